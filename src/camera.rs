@@ -1,8 +1,8 @@
 use std::f64::consts::PI;
 
 use crate::{
-    hit::Ray,
-    math::{cross, vec3, Vec3},
+    hit::{Differentials, Ray},
+    math::{cross, vec3, Vec2, Vec3},
 };
 
 pub struct Camera {
@@ -54,10 +54,16 @@ impl Camera {
         }
     }
 
-    pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-        Ray::new(
-            self.origin,
-            self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin,
-        )
+    // `pixel` is the spacing between neighbouring rays, it sets the footprint textures filter
+    // over
+    pub fn get_ray(&self, u: f64, v: f64, pixel: Vec2) -> Ray {
+        let direction =
+            self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin;
+        Ray::new(self.origin, direction).with_differentials(Some(Differentials {
+            rx_origin: self.origin,
+            rx_direction: direction + pixel.u() * self.horizontal,
+            ry_origin: self.origin,
+            ry_direction: direction + pixel.v() * self.vertical,
+        }))
     }
 }
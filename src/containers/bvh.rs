@@ -1,10 +1,72 @@
-use crate::hit::{Aabb, Hitable};
+use rand::random;
+
+use crate::hit::{surrounding_box, Aabb, Hitable, SurfaceSample};
 use crate::hit::{HitRecord, Ray};
+use crate::light::{area_pdf_value, area_random};
+use crate::math::Vec3;
 
 pub struct BvhNode {
     bbox: Aabb,
     left: Option<Box<dyn Hitable>>,
     right: Option<Box<dyn Hitable>>,
+    // cached so that sampling a surface doesn't walk the whole tree
+    area: f64,
+    power: Vec3,
+}
+
+impl BvhNode {
+    // splits at the median along the longest axis of the object centers
+    pub fn new(mut objects: Vec<Box<dyn Hitable>>) -> Self {
+        assert!(!objects.is_empty(), "bvh must not be empty");
+
+        let (left, right) = match objects.len() {
+            1 => (objects.pop(), None),
+            2 => {
+                let right = objects.pop();
+                (objects.pop(), right)
+            }
+            n => {
+                let first = center(objects[0].as_ref());
+                let (mut min, mut max) = (first, first);
+                for c in objects.iter().map(|h| center(h.as_ref())) {
+                    for a in 0..3 {
+                        min[a] = min[a].min(c[a]);
+                        max[a] = max[a].max(c[a]);
+                    }
+                }
+                let extent = max - min;
+                let axis = (0..3)
+                    .max_by(|a, b| extent[*a].total_cmp(&extent[*b]))
+                    .unwrap();
+
+                objects.sort_by(|a, b| {
+                    let (a, b) = (center(a.as_ref()), center(b.as_ref()));
+                    a[axis].total_cmp(&b[axis])
+                });
+                let right = objects.split_off(n / 2);
+                let left: Box<dyn Hitable> = Box::new(BvhNode::new(objects));
+                let right: Box<dyn Hitable> = Box::new(BvhNode::new(right));
+                (Some(left), Some(right))
+            }
+        };
+
+        let children = left.iter().chain(right.iter());
+        let bbox = children
+            .clone()
+            .map(|h| h.bounding_box())
+            .reduce(|a, b| surrounding_box(&a, &b))
+            .unwrap();
+        let area = children.clone().map(|h| h.area()).sum();
+        let power = children.map(|h| h.power()).sum();
+
+        Self {
+            bbox,
+            left,
+            right,
+            area,
+            power,
+        }
+    }
 }
 
 impl Hitable for BvhNode {
@@ -13,19 +75,9 @@ impl Hitable for BvhNode {
             .hit(r, t_min, t_max)
             .then(|| {
                 let hit_left = self.left.as_ref().and_then(|h| h.hit(r, t_min, t_max));
+                let t_max = hit_left.as_ref().map_or(t_max, |rec| rec.t);
                 let hit_right = self.right.as_ref().and_then(|h| h.hit(r, t_min, t_max));
-                match (hit_left, hit_right) {
-                    (Some(left_rec), Some(right_rec)) => {
-                        if left_rec.t < right_rec.t {
-                            Some(left_rec)
-                        } else {
-                            Some(right_rec)
-                        }
-                    }
-                    (Some(left_rec), _) => Some(left_rec),
-                    (_, Some(right_rec)) => Some(right_rec),
-                    _ => None,
-                }
+                hit_right.or(hit_left)
             })
             .flatten()
     }
@@ -34,11 +86,51 @@ impl Hitable for BvhNode {
         self.bbox
     }
 
-    fn pdf_value(&self, o: &crate::math::Vec3, v: &crate::math::Vec3) -> f64 {
-        todo!()
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        area_pdf_value(self, o, v)
     }
 
-    fn random(&self, o: &crate::math::Vec3) -> crate::math::Vec3 {
-        todo!()
+    fn random(&self, o: &Vec3) -> Vec3 {
+        area_random(self, o)
     }
+
+    // picks a child by area, so that the leaves are sampled uniformly over the whole surface
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        if self.area <= 0. {
+            return None;
+        }
+
+        let left_area = self.left.as_ref().map_or(0., |h| h.area());
+        let (child, area) = if random::<f64>() * self.area < left_area {
+            (self.left.as_ref()?, left_area)
+        } else {
+            (self.right.as_ref()?, self.area - left_area)
+        };
+
+        child.sample_surface().map(|mut sample| {
+            sample.pdf *= area / self.area;
+            sample
+        })
+    }
+
+    fn area(&self) -> f64 {
+        self.area
+    }
+
+    fn power(&self) -> Vec3 {
+        self.power
+    }
+
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        self.left
+            .iter()
+            .chain(self.right.iter())
+            .filter_map(|h| h.finite_bounding_box())
+            .reduce(|a, b| surrounding_box(&a, &b))
+    }
+}
+
+fn center(h: &dyn Hitable) -> Vec3 {
+    let bbox = h.bounding_box();
+    (bbox.min() + bbox.max()) / 2.
 }
@@ -1,8 +1,9 @@
-use rand::{prelude::SliceRandom, thread_rng};
+use rand::{prelude::SliceRandom, random, thread_rng};
 
 use crate::{
-    hit::{surrounding_box, Aabb, HitRecord, Hitable, Ray},
+    hit::{surrounding_box, Aabb, HitRecord, Hitable, Ray, SurfaceSample},
     math::{vec3, Vec3},
+    spectrum::luminance,
 };
 
 impl<T> Hitable for Vec<T>
@@ -24,6 +25,22 @@ where
     fn random(&self, o: &Vec3) -> Vec3 {
         self.as_slice().random(o)
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        self.as_slice().sample_surface()
+    }
+
+    fn area(&self) -> f64 {
+        self.as_slice().area()
+    }
+
+    fn power(&self) -> Vec3 {
+        self.as_slice().power()
+    }
+
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        self.as_slice().finite_bounding_box()
+    }
 }
 
 impl<T, const N: usize> Hitable for [T; N]
@@ -45,6 +62,22 @@ where
     fn random(&self, o: &Vec3) -> Vec3 {
         self.as_ref().random(o)
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        self.as_ref().sample_surface()
+    }
+
+    fn area(&self) -> f64 {
+        self.as_ref().area()
+    }
+
+    fn power(&self) -> Vec3 {
+        self.as_ref().power()
+    }
+
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        self.as_ref().finite_bounding_box()
+    }
 }
 
 impl<T> Hitable for [T]
@@ -86,4 +119,49 @@ where
             .map(|obj| obj.random(o))
             .unwrap_or(vec3(1, 0, 0))
     }
+
+    // emitters are picked proportional to their power, anything else by area
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        let mut weights: Vec<f64> = self.iter().map(|h| luminance(&h.power())).collect();
+        if weights.iter().all(|w| *w <= 0.) {
+            weights = self.iter().map(|h| h.area()).collect();
+        }
+
+        let total: f64 = weights.iter().sum();
+        if total <= 0. {
+            return None;
+        }
+
+        let mut target = random::<f64>() * total;
+        let mut chosen = None;
+        for (obj, weight) in self.iter().zip(weights) {
+            if weight > 0. {
+                chosen = Some((obj, weight));
+            }
+            target -= weight;
+            if target < 0. {
+                break;
+            }
+        }
+        let (obj, weight) = chosen?;
+
+        obj.sample_surface().map(|mut sample| {
+            sample.pdf *= weight / total;
+            sample
+        })
+    }
+
+    fn area(&self) -> f64 {
+        self.iter().map(|h| h.area()).sum()
+    }
+
+    fn power(&self) -> Vec3 {
+        self.iter().map(|h| h.power()).sum()
+    }
+
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        self.iter()
+            .filter_map(|h| h.finite_bounding_box())
+            .reduce(|a, b| surrounding_box(&a, &b))
+    }
 }
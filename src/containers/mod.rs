@@ -1,4 +1,5 @@
 mod bvh;
 mod list;
 
+pub use bvh::*;
 pub use list::*;
@@ -0,0 +1,204 @@
+// Constructive solid geometry. Closed hitables report every stretch of a ray that lies inside
+// them, combinators merge those stretches and hand out the boundaries as surface hits.
+
+use crate::{
+    hit::{surrounding_box, Aabb, HitRecord, Hitable, Ray},
+    math::{vec3, Vec3},
+};
+
+pub struct Interval<'m> {
+    pub enter: HitRecord<'m>,
+    pub exit: HitRecord<'m>,
+}
+
+pub trait Solid: Hitable {
+    // stretches of the whole line through `r` inside the solid, in order along the ray. Entries
+    // have `front_face` set, exits don't.
+    fn intervals(&self, r: &Ray) -> Vec<Interval>;
+}
+
+// pairs up the surface crossings of a closed hitable by walking along the ray
+pub fn boundary_intervals<'a>(shape: &'a (impl Hitable + ?Sized), r: &Ray) -> Vec<Interval<'a>> {
+    let mut intervals = Vec::new();
+    let mut enter = None;
+
+    // an unbounded shape can't be walked from infinitely far back, so the walk starts at the
+    // ray origin and a stretch the origin is already inside of starts there
+    let bounded = shape.bounding_box().is_finite();
+    let mut t = if bounded { f64::NEG_INFINITY } else { 0. };
+
+    while let Some(rec) = shape.hit(r, t, f64::INFINITY) {
+        t = rec.t + 0.0001;
+        if rec.front_face {
+            enter = Some(rec);
+        } else if let Some(enter) = enter.take() {
+            intervals.push(Interval { enter, exit: rec });
+        } else if !bounded && intervals.is_empty() {
+            let enter = HitRecord {
+                t: 0.,
+                p: *r.origin(),
+                front_face: true,
+                ..rec
+            };
+            intervals.push(Interval { enter, exit: rec });
+        }
+    }
+
+    intervals
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Operation {
+    Union,
+    Intersection,
+    // the first operand with the second carved out, cut faces take the second's material
+    Difference,
+}
+
+impl Operation {
+    fn inside(self, a: bool, b: bool) -> bool {
+        match self {
+            Operation::Union => a || b,
+            Operation::Intersection => a && b,
+            Operation::Difference => a && !b,
+        }
+    }
+}
+
+pub struct Csg<A, B> {
+    operation: Operation,
+    a: A,
+    b: B,
+}
+
+impl<A, B> Csg<A, B>
+where
+    A: Solid,
+    B: Solid,
+{
+    pub fn new(operation: Operation, a: A, b: B) -> Self {
+        Self { operation, a, b }
+    }
+}
+
+// merges the boundaries of both operands and keeps those where the inside of the result changes
+fn combine<'m>(
+    operation: Operation,
+    a: Vec<Interval<'m>>,
+    b: Vec<Interval<'m>>,
+) -> Vec<Interval<'m>> {
+    let events = |intervals: Vec<Interval<'m>>, from_a| {
+        intervals
+            .into_iter()
+            .flat_map(|interval| vec![interval.enter, interval.exit])
+            .map(move |rec| (rec, from_a))
+    };
+    let mut events: Vec<_> = events(a, true).chain(events(b, false)).collect();
+    events.sort_by(|(a, _), (b, _)| a.t.total_cmp(&b.t));
+
+    let mut intervals = Vec::new();
+    let (mut in_a, mut in_b) = (false, false);
+    let mut enter = None;
+    for (mut rec, from_a) in events {
+        let was_inside = operation.inside(in_a, in_b);
+        if from_a {
+            in_a = rec.front_face;
+        } else {
+            in_b = rec.front_face;
+        }
+        let inside = operation.inside(in_a, in_b);
+        if inside == was_inside {
+            continue;
+        }
+
+        // the normal already faces the ray, so flipping the side is enough to turn an exit of
+        // the carved out solid into an entry of the result
+        rec.front_face = inside;
+        if inside {
+            enter = Some(rec);
+        } else if let Some(enter) = enter.take() {
+            intervals.push(Interval { enter, exit: rec });
+        }
+    }
+
+    intervals
+}
+
+impl<A, B> Solid for Csg<A, B>
+where
+    A: Solid,
+    B: Solid,
+{
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        combine(self.operation, self.a.intervals(r), self.b.intervals(r))
+    }
+}
+
+impl<A, B> Hitable for Csg<A, B>
+where
+    A: Solid,
+    B: Solid,
+{
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.intervals(r)
+            .into_iter()
+            .flat_map(|interval| vec![interval.enter, interval.exit])
+            .find(|rec| t_min <= rec.t && rec.t <= t_max)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let (a, b) = (self.a.bounding_box(), self.b.bounding_box());
+        match self.operation {
+            Operation::Union => surrounding_box(&a, &b),
+            Operation::Intersection => {
+                let min = a.min();
+                let max = a.max();
+                Aabb::new(
+                    vec3(
+                        min.x().max(b.min().x()),
+                        min.y().max(b.min().y()),
+                        min.z().max(b.min().z()),
+                    ),
+                    vec3(
+                        max.x().min(b.max().x()),
+                        max.y().min(b.max().y()),
+                        max.z().min(b.max().z()),
+                    ),
+                )
+            }
+            Operation::Difference => a,
+        }
+    }
+
+    // the surface area of the result isn't known, so it can't be sampled as a light
+    fn pdf_value(&self, _o: &Vec3, _v: &Vec3) -> f64 {
+        0.0
+    }
+
+    fn random(&self, _o: &Vec3) -> Vec3 {
+        vec3(1, 0, 0)
+    }
+}
+
+pub trait SolidExt: Sized {
+    fn union<B: Solid>(self, other: B) -> Csg<Self, B>;
+    fn intersection<B: Solid>(self, other: B) -> Csg<Self, B>;
+    fn difference<B: Solid>(self, other: B) -> Csg<Self, B>;
+}
+
+impl<T> SolidExt for T
+where
+    T: Solid,
+{
+    fn union<B: Solid>(self, other: B) -> Csg<Self, B> {
+        Csg::new(Operation::Union, self, other)
+    }
+
+    fn intersection<B: Solid>(self, other: B) -> Csg<Self, B> {
+        Csg::new(Operation::Intersection, self, other)
+    }
+
+    fn difference<B: Solid>(self, other: B) -> Csg<Self, B> {
+        Csg::new(Operation::Difference, self, other)
+    }
+}
@@ -0,0 +1,159 @@
+use std::{f64::consts::PI, io, path::Path};
+
+use rand::random;
+
+use crate::{
+    image::Image,
+    math::{random_unit_vector, vec2, vec3, Distribution2D, Vec2, Vec3},
+    spectrum::luminance,
+};
+
+// radiance arriving from infinitely far away, looked up by rays that miss the world
+pub trait Environment: Send + Sync {
+    fn value(&self, direction: &Vec3) -> Vec3;
+
+    // whether the integrator should sample the environment for next-event estimation
+    fn sampled(&self) -> bool {
+        false
+    }
+
+    fn pdf_value(&self, direction: &Vec3) -> f64 {
+        let _ = direction;
+        1. / (4. * PI)
+    }
+
+    fn random(&self) -> Vec3 {
+        random_unit_vector()
+    }
+}
+
+impl Environment for Vec3 {
+    fn value(&self, _direction: &Vec3) -> Vec3 {
+        *self
+    }
+}
+
+pub struct Gradient {
+    horizon: Vec3,
+    zenith: Vec3,
+}
+
+impl Gradient {
+    pub fn new(horizon: Vec3, zenith: Vec3) -> Self {
+        Self { horizon, zenith }
+    }
+
+    pub fn sky() -> Self {
+        Self::new(vec3(1., 1., 1.), vec3(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for Gradient {
+    fn value(&self, direction: &Vec3) -> Vec3 {
+        let t = 0.5 * (direction.normalize().y() + 1.);
+        (1. - t) * self.horizon + t * self.zenith
+    }
+}
+
+// lat-long map, u follows the azimuth around +y and v runs from +y (top row) to -y
+pub struct ImageEnvironment {
+    image: Image,
+    distribution: Distribution2D,
+    sin_rotation: f64,
+    cos_rotation: f64,
+    intensity: f64,
+}
+
+impl ImageEnvironment {
+    pub fn new(image: Image) -> Self {
+        let (width, height) = (image.width(), image.height());
+        let weights: Vec<f64> = (0..height)
+            .flat_map(|y| {
+                let sin_theta = f64::sin(PI * (y as f64 + 0.5) / height as f64);
+                let image = &image;
+                (0..width).map(move |x| luminance(&image.get(x, y)) * sin_theta)
+            })
+            .collect();
+
+        Self {
+            distribution: Distribution2D::new(&weights, width, height),
+            image,
+            sin_rotation: 0.,
+            cos_rotation: 1.,
+            intensity: 1.,
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(Image::open(path)?))
+    }
+
+    // rotation around the y axis, in degrees
+    pub fn rotate(self, angle: f64) -> Self {
+        let (sin_rotation, cos_rotation) = angle.to_radians().sin_cos();
+        Self {
+            sin_rotation,
+            cos_rotation,
+            ..self
+        }
+    }
+
+    pub fn intensity(self, intensity: f64) -> Self {
+        Self { intensity, ..self }
+    }
+
+    fn to_local(&self, d: &Vec3) -> Vec3 {
+        vec3(
+            self.cos_rotation * d.x() - self.sin_rotation * d.z(),
+            d.y(),
+            self.sin_rotation * d.x() + self.cos_rotation * d.z(),
+        )
+    }
+
+    fn to_world(&self, d: &Vec3) -> Vec3 {
+        vec3(
+            self.cos_rotation * d.x() + self.sin_rotation * d.z(),
+            d.y(),
+            -self.sin_rotation * d.x() + self.cos_rotation * d.z(),
+        )
+    }
+}
+
+fn direction_to_uv(d: &Vec3) -> Vec2 {
+    let theta = f64::acos(d.y().clamp(-1., 1.));
+    let phi = f64::atan2(d.z(), d.x()) + PI;
+    vec2(phi / (2. * PI), theta / PI)
+}
+
+fn uv_to_direction(uv: Vec2) -> Vec3 {
+    let (sin_theta, cos_theta) = (uv.v() * PI).sin_cos();
+    let (sin_phi, cos_phi) = (uv.u() * 2. * PI - PI).sin_cos();
+    vec3(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi)
+}
+
+impl Environment for ImageEnvironment {
+    fn value(&self, direction: &Vec3) -> Vec3 {
+        let uv = direction_to_uv(&self.to_local(&direction.normalize()));
+        let x = (uv.u() * self.image.width() as f64) as usize;
+        let y = (uv.v() * self.image.height() as f64) as usize;
+        self.intensity * self.image.get(x, y)
+    }
+
+    fn sampled(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, direction: &Vec3) -> f64 {
+        let uv = direction_to_uv(&self.to_local(&direction.normalize()));
+        let sin_theta = f64::sin(uv.v() * PI);
+        if sin_theta <= 0. {
+            return 0.;
+        }
+        self.distribution.pdf(uv) / (2. * PI * PI * sin_theta)
+    }
+
+    fn random(&self) -> Vec3 {
+        let (uv, _pdf) = self.distribution.sample(vec2(random(), random()));
+        self.to_world(&uv_to_direction(uv))
+    }
+}
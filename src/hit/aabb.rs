@@ -20,6 +20,10 @@ impl Aabb {
         self.max
     }
 
+    pub fn is_finite(&self) -> bool {
+        (0..3).all(|a| self.min[a].is_finite() && self.max[a].is_finite())
+    }
+
     pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
         (0..3).all(|a| {
             let inv_d = 1.0 / r.direction()[a];
@@ -37,6 +41,22 @@ impl Aabb {
             t_max > t_min
         })
     }
+
+    // the part of [t_min, t_max] where `r` is inside the box
+    pub fn interval(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        for a in 0..3 {
+            let inv_d = 1.0 / r.direction()[a];
+            let t0 = (self.min[a] - r.origin()[a]) * inv_d;
+            let t1 = (self.max[a] - r.origin()[a]) * inv_d;
+            let (t0, t1) = if inv_d < 0.0 { (t1, t0) } else { (t0, t1) };
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
 }
 
 pub fn surrounding_box(box0: &Aabb, box1: &Aabb) -> Aabb {
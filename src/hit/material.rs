@@ -1,6 +1,10 @@
 use std::sync::Arc;
 
-use crate::math::{Vec2, Vec3};
+use crate::{
+    math::{Vec2, Vec3},
+    spectrum::upsample,
+    volume::Medium,
+};
 
 use super::{HitRecord, Ray};
 
@@ -35,6 +39,17 @@ impl Scatter {
     pub fn kind(&self) -> &ScatterKind {
         &self.kind
     }
+
+    pub fn into_parts(self) -> (Vec3, ScatterKind) {
+        (self.attenuation, self.kind)
+    }
+
+    pub fn attenuated(self, factor: Vec3) -> Self {
+        Self {
+            attenuation: factor * self.attenuation,
+            ..self
+        }
+    }
 }
 
 pub trait Material: Send + Sync {
@@ -49,6 +64,53 @@ pub trait Material: Send + Sync {
         let _ = (r_in, rec, uv, p);
         Vec3::zero()
     }
+
+    // emission at the wavelengths of a spectral path
+    fn emitted_spectral(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        uv: Vec2,
+        p: &Vec3,
+        lambdas: &Vec3,
+    ) -> Vec3 {
+        upsample(&self.emitted(r_in, rec, uv, p), lambdas)
+    }
+
+    fn power(&self, area: f64) -> Vec3 {
+        let _ = area;
+        Vec3::zero()
+    }
+
+    // emits from the back of the surface as well as the front
+    fn is_two_sided(&self) -> bool {
+        false
+    }
+
+    // scatters inside a medium, where the hit record's normal carries no meaning
+    fn is_volumetric(&self) -> bool {
+        false
+    }
+
+    // medium filling the inside of the surface, the side the outward normal points away from
+    fn interior(&self) -> Option<&dyn Medium> {
+        None
+    }
+
+    // the surface only separates media, rays and shadow rays continue straight through it
+    fn is_interface(&self) -> bool {
+        false
+    }
+
+    // index of refraction of the inside, for dielectrics
+    fn ior(&self) -> Option<f64> {
+        None
+    }
+
+    // where nested volumes overlap, the one with the highest priority fills the overlap
+    fn priority(&self) -> u32 {
+        0
+    }
 }
 
 pub trait MatPtr {
@@ -93,3 +155,13 @@ impl Pdf for &dyn Pdf {
         (*self).generate()
     }
 }
+
+impl Pdf for Box<dyn Pdf> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.as_ref().value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.as_ref().generate()
+    }
+}
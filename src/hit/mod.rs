@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::math::{dot, Vec2, Vec3};
+use crate::math::{dot, reflect, refract, Vec2, Vec3};
 
 mod aabb;
 pub use aabb::surrounding_box;
@@ -13,11 +13,50 @@ pub use material::*;
 pub struct Ray {
     a: Vec3,
     b: Vec3,
+    wavelengths: Option<Vec3>,
+    differentials: Option<Differentials>,
+}
+
+// rays through the neighbouring pixels in x and y, tracked alongside camera and specular paths
+#[derive(Debug, Clone, Copy)]
+pub struct Differentials {
+    pub rx_origin: Vec3,
+    pub rx_direction: Vec3,
+    pub ry_origin: Vec3,
+    pub ry_direction: Vec3,
 }
 
 impl Ray {
     pub fn new(a: Vec3, b: Vec3) -> Self {
-        Self { a, b }
+        Self {
+            a,
+            b,
+            wavelengths: None,
+            differentials: None,
+        }
+    }
+
+    // wavelengths in nm carried by a path in spectral mode, terminated ones are NaN
+    pub fn with_wavelengths(self, wavelengths: Option<Vec3>) -> Self {
+        Self {
+            wavelengths,
+            ..self
+        }
+    }
+
+    pub fn wavelengths(&self) -> Option<Vec3> {
+        self.wavelengths
+    }
+
+    pub fn with_differentials(self, differentials: Option<Differentials>) -> Self {
+        Self {
+            differentials,
+            ..self
+        }
+    }
+
+    pub fn differentials(&self) -> Option<Differentials> {
+        self.differentials
     }
 
     pub fn direction(&self) -> &Vec3 {
@@ -36,10 +75,35 @@ impl Ray {
 pub struct HitRecord<'m> {
     pub t: f64,
     pub p: Vec3,
+    // where the shape itself was hit, before any transforms moved it into the world
+    pub local_p: Vec3,
     pub normal: Vec3,
     pub front_face: bool,
     pub material: &'m dyn Material,
     pub uv: Vec2,
+    // change of the position with the texture coordinates, zero where a shape has no tangents
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    // index of refraction on the side the outward normal points to, filled in by the integrator
+    pub outer_ior: f64,
+    // area seen through a pixel, zero without ray differentials
+    pub footprint: Footprint,
+}
+
+// change of the position and texture coordinates from one pixel to the next
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Footprint {
+    pub dpdx: Vec3,
+    pub dpdy: Vec3,
+    pub duvdx: Vec2,
+    pub duvdy: Vec2,
+}
+
+impl Footprint {
+    // extent in world space, for textures that are evaluated on the position
+    pub fn width(&self) -> f64 {
+        self.dpdx.length().max(self.dpdy.length())
+    }
 }
 
 impl<'m> HitRecord<'m> {
@@ -61,13 +125,96 @@ impl<'m> HitRecord<'m> {
         Self {
             t,
             p,
+            local_p: p,
             normal,
             front_face,
             material,
             uv,
+            dpdu: Vec3::zero(),
+            dpdv: Vec3::zero(),
+            outer_ior: 1.,
+            footprint: Footprint::default(),
         }
     }
 
+    pub fn tangents(self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        Self { dpdu, dpdv, ..self }
+    }
+
+    // the normal on the outside, whichever side the ray came from
+    pub fn outward_normal(&self) -> Vec3 {
+        if self.front_face {
+            self.normal
+        } else {
+            -self.normal
+        }
+    }
+
+    // intersects the neighbouring rays with the tangent plane and projects the offsets onto the
+    // tangents to get the change in texture coordinates
+    pub fn set_footprint(&mut self, r: &Ray) {
+        let d = match r.differentials() {
+            Some(d) => d,
+            None => return,
+        };
+        let offset = |o: &Vec3, dir: &Vec3| {
+            let cos = dot(&self.normal, dir);
+            if cos.abs() < 1e-12 {
+                return None;
+            }
+            let t = dot(&self.normal, &(self.p - o)) / cos;
+            Some(o + t * dir - self.p)
+        };
+        let (dpdx, dpdy) = match (
+            offset(&d.rx_origin, &d.rx_direction),
+            offset(&d.ry_origin, &d.ry_direction),
+        ) {
+            (Some(dpdx), Some(dpdy)) => (dpdx, dpdy),
+            _ => return,
+        };
+        self.footprint.dpdx = dpdx;
+        self.footprint.dpdy = dpdy;
+
+        // least squares through the normal equations
+        let a = self.dpdu.length_squared();
+        let b = dot(&self.dpdu, &self.dpdv);
+        let c = self.dpdv.length_squared();
+        let det = a * c - b * b;
+        if det.abs() < 1e-12 {
+            return;
+        }
+        let (dpdu, dpdv) = (self.dpdu, self.dpdv);
+        let solve = |dp: &Vec3| {
+            let (pu, pv) = (dot(&dpdu, dp), dot(&dpdv, dp));
+            Vec2::new((c * pu - b * pv) / det, (a * pv - b * pu) / det)
+        };
+        self.footprint.duvdx = solve(&dpdx);
+        self.footprint.duvdy = solve(&dpdy);
+    }
+
+    // differentials of a ray mirrored at the hit, the surface is taken to be locally flat
+    pub fn reflect_differentials(&self, r_in: &Ray) -> Option<Differentials> {
+        let d = r_in.differentials()?;
+        Some(Differentials {
+            rx_origin: self.p + self.footprint.dpdx,
+            rx_direction: reflect(&d.rx_direction, &self.normal),
+            ry_origin: self.p + self.footprint.dpdy,
+            ry_direction: reflect(&d.ry_direction, &self.normal),
+        })
+    }
+
+    // differentials of a ray refracted at the hit with the ratio of the indices `eta`
+    pub fn refract_differentials(&self, r_in: &Ray, eta: f64) -> Option<Differentials> {
+        let d = r_in.differentials()?;
+        let refract = |v: &Vec3| refract(&v.normalize(), &self.normal, eta);
+        Some(Differentials {
+            rx_origin: self.p + self.footprint.dpdx,
+            rx_direction: refract(&d.rx_direction),
+            ry_origin: self.p + self.footprint.dpdy,
+            ry_direction: refract(&d.ry_direction),
+        })
+    }
+
     pub fn set_face_normal(&mut self, r: &Ray, normal: Vec3) {
         let front_face = dot(r.direction(), &normal) < 0.;
         let normal = if front_face { normal } else { -normal };
@@ -76,6 +223,22 @@ impl<'m> HitRecord<'m> {
     }
 }
 
+pub struct SurfaceSample<'m> {
+    pub p: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+    pub pdf: f64,
+    pub material: &'m dyn Material,
+}
+
+impl SurfaceSample<'_> {
+    pub fn emitted(&self, direction: &Vec3) -> Vec3 {
+        let r = Ray::new(self.p + direction, -*direction);
+        let rec = HitRecord::new(&r, 1., self.p, self.normal, self.uv, self.material);
+        self.material.emitted(&r, &rec, self.uv, &self.p)
+    }
+}
+
 pub trait Hitable: Send + Sync {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
     fn bounding_box(&self) -> Aabb;
@@ -83,6 +246,32 @@ pub trait Hitable: Send + Sync {
     fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64;
 
     fn random(&self, o: &Vec3) -> Vec3;
+
+    /// Picks a point on the surface, with `pdf` measured with respect to surface area.
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        None
+    }
+
+    /// The point at texture coordinates `uv`, with `pdf` the area density of uniformly
+    /// distributed `uv`. Lets emitters be sampled by what their textures emit.
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        let _ = uv;
+        None
+    }
+
+    fn area(&self) -> f64 {
+        0.0
+    }
+
+    /// Total emitted power, summed over everything that emits.
+    fn power(&self) -> Vec3 {
+        Vec3::zero()
+    }
+
+    /// Bounds of everything finite in it, leaving out unbounded shapes such as planes.
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounding_box()).filter(Aabb::is_finite)
+    }
 }
 
 impl Hitable for Box<dyn Hitable> {
@@ -101,6 +290,26 @@ impl Hitable for Box<dyn Hitable> {
     fn random(&self, o: &Vec3) -> Vec3 {
         (&**self).random(o)
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        (&**self).sample_surface()
+    }
+
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        (&**self).sample_uv(uv)
+    }
+
+    fn area(&self) -> f64 {
+        (&**self).area()
+    }
+
+    fn power(&self) -> Vec3 {
+        (&**self).power()
+    }
+
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        (&**self).finite_bounding_box()
+    }
 }
 
 impl Hitable for Arc<dyn Hitable> {
@@ -119,6 +328,26 @@ impl Hitable for Arc<dyn Hitable> {
     fn random(&self, o: &Vec3) -> Vec3 {
         (&**self).random(o)
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        (&**self).sample_surface()
+    }
+
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        (&**self).sample_uv(uv)
+    }
+
+    fn area(&self) -> f64 {
+        (&**self).area()
+    }
+
+    fn power(&self) -> Vec3 {
+        (&**self).power()
+    }
+
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        (&**self).finite_bounding_box()
+    }
 }
 
 impl<T> Hitable for &'_ T
@@ -140,4 +369,24 @@ where
     fn random(&self, o: &Vec3) -> Vec3 {
         (*self).random(o)
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        (*self).sample_surface()
+    }
+
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        (*self).sample_uv(uv)
+    }
+
+    fn area(&self) -> f64 {
+        (*self).area()
+    }
+
+    fn power(&self) -> Vec3 {
+        (*self).power()
+    }
+
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        (*self).finite_bounding_box()
+    }
 }
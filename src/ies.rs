@@ -0,0 +1,237 @@
+// IES LM-63 photometric files, the angular intensity distributions lamp manufacturers publish
+// for their luminaires. Only type C photometry is read, the kind used for nearly every indoor
+// and street light.
+
+use std::{f64::consts::PI, fs, io, path::Path};
+
+use crate::math::Vec3;
+
+pub struct IesProfile {
+    // angles from the nadir, ascending, in degrees
+    vertical: Vec<f64>,
+    // angles around the vertical axis from the C0 plane, ascending, in degrees
+    horizontal: Vec<f64>,
+    // candela for each horizontal angle, over the vertical angles
+    candela: Vec<Vec<f64>>,
+    peak: f64,
+    // luminous flux below and above the horizon
+    flux_down: f64,
+    flux_up: f64,
+}
+
+impl IesProfile {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut lines = text.lines();
+        let tilt = loop {
+            let line = lines
+                .next()
+                .ok_or_else(|| invalid_data("missing TILT line"))?;
+            if let Some(tilt) = line.trim().strip_prefix("TILT=") {
+                break tilt.trim();
+            }
+        };
+
+        let mut numbers = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                word.parse::<f64>()
+                    .map_err(|_| invalid_data("malformed number"))
+            });
+        let mut next = || {
+            numbers
+                .next()
+                .unwrap_or_else(|| Err(invalid_data("unexpected end of file")))
+        };
+
+        match tilt {
+            "NONE" => {}
+            // lamp to luminaire geometry and the tilt angle and factor pairs, the lamp is
+            // assumed to be mounted as measured
+            "INCLUDE" => {
+                next()?;
+                let pairs = next()? as usize;
+                for _ in 0..2 * pairs {
+                    next()?;
+                }
+            }
+            _ => return Err(invalid_data("external tilt files are not supported")),
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()?;
+        let _units = next()?;
+        let _dimensions = [next()?, next()?, next()?];
+        let ballast_factor = next()?;
+        let ballast_lamp_factor = next()?;
+        let _input_watts = next()?;
+
+        if photometric_type != 1. {
+            return Err(invalid_data("only type C photometry is supported"));
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(invalid_data("no candela values"));
+        }
+
+        let vertical = (0..vertical_count)
+            .map(|_| next())
+            .collect::<io::Result<Vec<_>>>()?;
+        let horizontal = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<io::Result<Vec<_>>>()?;
+        let ascending = |angles: &[f64]| angles.windows(2).all(|w| w[0] < w[1]);
+        if !ascending(&vertical) || !ascending(&horizontal) {
+            return Err(invalid_data("angles must be ascending"));
+        }
+
+        let scale = multiplier * ballast_factor * ballast_lamp_factor;
+        let candela = (0..horizontal_count)
+            .map(|_| {
+                (0..vertical_count)
+                    .map(|_| next().map(|c| scale * c.max(0.)))
+                    .collect::<io::Result<Vec<_>>>()
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut profile = Self {
+            peak: candela.iter().flatten().cloned().fold(0., f64::max),
+            vertical,
+            horizontal,
+            candela,
+            flux_down: 0.,
+            flux_up: 0.,
+        };
+        profile.integrate();
+        Ok(profile)
+    }
+
+    // luminous intensity in cd towards `direction`, given in the luminaire's frame: z along
+    // the nadir, x in the C0 and y in the C90 plane
+    pub fn candela(&self, direction: &Vec3) -> f64 {
+        let d = direction.normalize();
+        let vertical = d.z().clamp(-1., 1.).acos().to_degrees();
+        let horizontal = d.y().atan2(d.x()).to_degrees();
+        self.candela_at(vertical, horizontal)
+    }
+
+    // candela in the brightest direction
+    pub fn peak(&self) -> f64 {
+        self.peak
+    }
+
+    // total luminous flux, in lm
+    pub fn lumens(&self) -> f64 {
+        self.flux_down + self.flux_up
+    }
+
+    // flux into the half space below the luminaire, with `upper` the one above
+    pub fn hemisphere_lumens(&self, upper: bool) -> f64 {
+        if upper {
+            self.flux_up
+        } else {
+            self.flux_down
+        }
+    }
+
+    fn candela_at(&self, vertical: f64, horizontal: f64) -> f64 {
+        let (h0, h1, s) = match self.horizontal_segment(horizontal) {
+            Some(segment) => segment,
+            None => return 0.,
+        };
+        let (v0, v1, t) = match segment(&self.vertical, vertical) {
+            Some(segment) => segment,
+            None => return 0.,
+        };
+        let row = |h: usize| (1. - t) * self.candela[h][v0] + t * self.candela[h][v1];
+        (1. - s) * row(h0) + s * row(h1)
+    }
+
+    // the data covers a single angle for rotationally symmetric luminaires, one quadrant or one
+    // half for luminaires symmetric about both planes or one of them, or the full circle
+    fn horizontal_segment(&self, horizontal: f64) -> Option<(usize, usize, f64)> {
+        let angles = &self.horizontal;
+        let last = angles[angles.len() - 1];
+        let phi = horizontal.rem_euclid(360.);
+
+        if angles.len() == 1 {
+            return Some((0, 0, 0.));
+        }
+        let phi = if last <= 90. {
+            let half = phi.rem_euclid(180.);
+            half.min(180. - half)
+        } else if last <= 180. {
+            phi.min(360. - phi)
+        } else if angles[0] == 90. && last == 270. {
+            // older files give the half symmetric about the C90-C270 plane
+            if (90. ..=270.).contains(&phi) {
+                phi
+            } else {
+                (540. - phi).rem_euclid(360.)
+            }
+        } else {
+            phi
+        };
+
+        // the full circle closes from the last angle back round to the first
+        if phi > last {
+            let span = angles[0] + 360. - last;
+            return Some((angles.len() - 1, 0, (phi - last) / span));
+        }
+        if phi < angles[0] {
+            let span = angles[0] + 360. - last;
+            return Some((angles.len() - 1, 0, (phi + 360. - last) / span));
+        }
+        segment(angles, phi)
+    }
+
+    // midpoint rule over the sphere
+    fn integrate(&mut self) {
+        let (rows, columns) = (180, 360);
+        let (d_theta, d_phi) = (PI / rows as f64, 2. * PI / columns as f64);
+        for i in 0..rows {
+            let theta = (i as f64 + 0.5) * d_theta;
+            let flux: f64 = (0..columns)
+                .map(|j| {
+                    let phi = (j as f64 + 0.5) * d_phi;
+                    self.candela_at(theta.to_degrees(), phi.to_degrees())
+                })
+                .sum::<f64>()
+                * theta.sin()
+                * d_theta
+                * d_phi;
+            if theta < PI / 2. {
+                self.flux_down += flux;
+            } else {
+                self.flux_up += flux;
+            }
+        }
+    }
+}
+
+// the two angles around `x` and how far it is from the first to the second, none outside
+fn segment(angles: &[f64], x: f64) -> Option<(usize, usize, f64)> {
+    let (first, last) = (angles[0], angles[angles.len() - 1]);
+    if x < first || x > last {
+        return None;
+    }
+    if angles.len() == 1 {
+        return Some((0, 0, 0.));
+    }
+    let i = angles
+        .partition_point(|a| *a <= x)
+        .clamp(1, angles.len() - 1);
+    let t = (x - angles[i - 1]) / (angles[i] - angles[i - 1]);
+    Some((i - 1, i, t))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,175 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+use crate::math::{vec3, Vec3};
+
+// linear radiance values, row 0 at the top
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count must match size");
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("hdr") | Some("pic") => read_hdr(BufReader::new(File::open(path)?)),
+            Some("exr") => read_exr(path),
+            _ => Err(invalid_data("unsupported image format")),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Vec3] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Vec3 {
+        self.pixels[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_exr(path: &Path) -> io::Result<Image> {
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        path,
+        |resolution, _channels| Image {
+            width: resolution.width(),
+            height: resolution.height(),
+            pixels: vec![Vec3::zero(); resolution.width() * resolution.height()],
+        },
+        |image: &mut Image, position, (r, g, b, _a): (f32, f32, f32, f32)| {
+            image.pixels[position.y() * image.width + position.x()] = vec3(r, g, b);
+        },
+    )
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    Ok(image.layer_data.channel_data.pixels)
+}
+
+// Radiance RGBE, flat or with new-style run length encoded scanlines
+fn read_hdr(mut reader: impl BufRead) -> io::Result<Image> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid_data("missing radiance header"));
+    }
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid_data("unexpected end of header"));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(invalid_data("unsupported pixel format"));
+        }
+    }
+
+    line.clear();
+    reader.read_line(&mut line)?;
+    let (height, width) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", h, "+X", w] => (
+            h.parse().map_err(|_| invalid_data("invalid height"))?,
+            w.parse().map_err(|_| invalid_data("invalid width"))?,
+        ),
+        _ => return Err(invalid_data("unsupported image orientation")),
+    };
+
+    let mut pixels = Vec::with_capacity(width * height);
+    let mut scanline = vec![[0u8; 4]; width];
+    for _y in 0..height {
+        read_scanline(&mut reader, &mut scanline)?;
+        pixels.extend(scanline.iter().map(rgbe_to_rgb));
+    }
+
+    Ok(Image::new(width, height, pixels))
+}
+
+fn read_scanline(reader: &mut impl Read, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    let mut rgbe = [0u8; 4];
+    reader.read_exact(&mut rgbe)?;
+
+    let rle = (8..0x8000).contains(&width) && rgbe[0] == 2 && rgbe[1] == 2 && rgbe[2] < 128;
+    if !rle {
+        scanline[0] = rgbe;
+        for pixel in &mut scanline[1..] {
+            reader.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+
+    if ((rgbe[2] as usize) << 8 | rgbe[3] as usize) != width {
+        return Err(invalid_data("scanline width mismatch"));
+    }
+
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 2];
+            reader.read_exact(&mut count[..1])?;
+            if count[0] > 128 {
+                let run = (count[0] - 128) as usize;
+                reader.read_exact(&mut count[1..])?;
+                if x + run > width {
+                    return Err(invalid_data("run exceeds scanline"));
+                }
+                scanline[x..x + run]
+                    .iter_mut()
+                    .for_each(|p| p[channel] = count[1]);
+                x += run;
+            } else {
+                let run = count[0] as usize;
+                if run == 0 || x + run > width {
+                    return Err(invalid_data("run exceeds scanline"));
+                }
+                for pixel in &mut scanline[x..x + run] {
+                    reader.read_exact(&mut count[1..])?;
+                    pixel[channel] = count[1];
+                }
+                x += run;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn rgbe_to_rgb(rgbe: &[u8; 4]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::zero();
+    }
+    let f = 2f64.powi(rgbe[3] as i32 - 136);
+    vec3(
+        rgbe[0] as f64 + 0.5,
+        rgbe[1] as f64 + 0.5,
+        rgbe[2] as f64 + 0.5,
+    ) * f
+}
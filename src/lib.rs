@@ -1,74 +1,228 @@
 use std::{f64::consts::FRAC_PI_4, sync::Arc};
 
 use camera::Camera;
+use environment::Environment;
 use hit::{Hitable, Pdf, Ray, ScatterKind};
 use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
+use light::PunctualLight;
 use materials::{Dielectric, DiffuseLight, Lambertian, Metal};
 use math::{vec2, vec3, Vec3};
 use objects::sphere::Sphere;
 use objects::{cuboid::Cuboid, rect::XyRect};
-use pdf::{HitablePdf, MixturePdf};
-use rand::{thread_rng, Rng};
+use pdf::{EnvironmentPdf, HitablePdf, MixturePdf};
+use rand::{random, thread_rng, Rng};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use spectrum::{sample_wavelengths, spectral_to_rgb, termination_weight, upsample};
 use texture::{Constant, Noise};
 use transform::HitableExt;
-use volume::ConstantMedium;
+use volume::{phase_value, ConstantMedium, Medium, MediumStack, PhasePdf};
 
 pub mod camera;
 pub mod containers;
+pub mod csg;
+pub mod environment;
 pub mod hit;
+pub mod ies;
+pub mod image;
+pub mod light;
 pub mod materials;
 pub mod math;
+pub mod mesh;
 pub mod objects;
 pub mod pdf;
+pub mod photon;
+pub mod sdf;
+pub mod sky;
+pub mod spectrum;
 pub mod texture;
 pub mod transform;
 pub mod volume;
 
-fn color(
-    r: &Ray,
-    world: &dyn Hitable,
-    lights: Option<&dyn Hitable>,
-    background: &Vec3,
-    depth: usize,
-) -> Vec3 {
+fn color<'m>(r: &Ray, scene: &'m Scene, depth: usize, stack: &MediumStack<'m>) -> Vec3 {
     if depth == 0 {
         return Vec3::zero();
     }
 
-    let rec = match world.hit(r, 0.001, f64::INFINITY) {
-        None => return *background,
+    let hit = scene.world.hit(r, 0.001, f64::INFINITY);
+
+    let mut weight = vec3(1, 1, 1);
+    if let Some(medium) = stack.medium() {
+        let t_max = hit.as_ref().map_or(f64::INFINITY, |rec| rec.t);
+        let sample = medium.sample(r, t_max);
+        if let Some(t) = sample.t {
+            return sample.weight * in_scatter(r, r.at(t), scene, depth, stack, medium);
+        }
+        if sample.weight.near_zero() {
+            return Vec3::zero();
+        }
+        weight = sample.weight;
+    }
+
+    let mut rec = match hit {
+        None => return weight * at_wavelengths(&scene.background.value(r.direction()), r),
         Some(rec) => rec,
     };
-    let emitted = rec.material.emitted(r, &rec, rec.uv, &rec.p);
+
+    // inside a volume of higher priority, the surface is not really there
+    if stack.is_false_intersection(&rec) {
+        let next = stack.cross(&rec, r.direction(), r.direction());
+        let r = Ray::new(rec.p, *r.direction())
+            .with_wavelengths(r.wavelengths())
+            .with_differentials(r.differentials());
+        return weight * color(&r, scene, depth, &next);
+    }
+    rec.outer_ior = stack.outer_ior(rec.material);
+    rec.set_footprint(r);
+
+    let emitted = match r.wavelengths() {
+        Some(lambdas) => rec
+            .material
+            .emitted_spectral(r, &rec, rec.uv, &rec.p, &lambdas),
+        None => rec.material.emitted(r, &rec, rec.uv, &rec.p),
+    };
 
     let scatter = match rec.material.scatter(r, &rec) {
-        None => return emitted,
+        None => return weight * emitted,
         Some(scatter) => scatter,
     };
+    let attenuation = at_wavelengths(scatter.attenuation(), r);
+
+    weight
+        * match scatter.kind() {
+            ScatterKind::Diffuse { pdf } => {
+                let (scattered, pdf) = sample_scattered(scene, rec.p, pdf.as_ref());
+                let scattered = scattered.with_wavelengths(r.wavelengths());
+                let direct = sample_punctual(scene, &rec.p, r.wavelengths(), |shadow_ray| {
+                    let f = attenuation * rec.material.scattering_pdf(r, &rec, shadow_ray);
+                    (f, stack.cross(&rec, r.direction(), shadow_ray.direction()))
+                });
+
+                let next = stack.cross(&rec, r.direction(), scattered.direction());
+                emitted
+                    + direct
+                    + attenuation
+                        * rec.material.scattering_pdf(r, &rec, &scattered)
+                        * color(&scattered, scene, depth - 1, &next)
+                        / pdf
+            }
+            ScatterKind::Specular { specular_ray } => {
+                let (specular_ray, dispersion) = follow(r, specular_ray);
+                let next = stack.cross(&rec, r.direction(), specular_ray.direction());
+                emitted + dispersion * attenuation * color(&specular_ray, scene, depth - 1, &next)
+            }
+        }
+}
+
+// rgb colours are upsampled to spectra for paths in spectral mode
+fn at_wavelengths(rgb: &Vec3, r: &Ray) -> Vec3 {
+    match r.wavelengths() {
+        Some(lambdas) => upsample(rgb, &lambdas),
+        None => *rgb,
+    }
+}
+
+// carries the path's wavelengths over to a scattered ray, and weights the ones that survive if
+// the scattering terminated the others
+fn follow(r_in: &Ray, scattered: &Ray) -> (Ray, Vec3) {
+    match (r_in.wavelengths(), scattered.wavelengths()) {
+        (Some(before), Some(after)) => (*scattered, termination_weight(&before, &after)),
+        (wavelengths, _) => (scattered.with_wavelengths(wavelengths), vec3(1, 1, 1)),
+    }
+}
+
+// scattering event at `p` inside `medium`, sampled like a diffuse surface with the phase function
+fn in_scatter<'m>(
+    r: &Ray,
+    p: Vec3,
+    scene: &'m Scene,
+    depth: usize,
+    stack: &MediumStack<'m>,
+    medium: &dyn Medium,
+) -> Vec3 {
+    let phase = medium.phase_function();
+    let phase_pdf = PhasePdf::new(phase.clone(), r.direction());
+    let (scattered, pdf) = sample_scattered(scene, p, &phase_pdf);
+    let scattered = scattered.with_wavelengths(r.wavelengths());
+
+    let direct = sample_punctual(scene, &p, r.wavelengths(), |shadow_ray| {
+        let f = phase_value(phase.as_ref(), r, shadow_ray);
+        (vec3(f, f, f), stack.clone())
+    });
+
+    direct
+        + phase_value(phase.as_ref(), r, &scattered) * color(&scattered, scene, depth - 1, stack)
+            / pdf
+}
+
+// mixes `pdf` with light and environment sampling where the scene has them
+fn sample_scattered(scene: &Scene, o: Vec3, pdf: &dyn Pdf) -> (Ray, f64) {
+    let light_pdf = scene
+        .lights
+        .as_deref()
+        .map(|lights| HitablePdf::new(o, lights));
+    let env_pdf = Some(scene.background.as_ref())
+        .filter(|env| env.sampled())
+        .map(EnvironmentPdf::new);
+
+    match (light_pdf, env_pdf) {
+        (Some(light_pdf), Some(env_pdf)) => sample_pdf(
+            o,
+            &MixturePdf::new(MixturePdf::new(light_pdf, env_pdf), pdf),
+        ),
+        (Some(light_pdf), None) => sample_pdf(o, &MixturePdf::new(light_pdf, pdf)),
+        (None, Some(env_pdf)) => sample_pdf(o, &MixturePdf::new(env_pdf, pdf)),
+        (None, None) => sample_pdf(o, pdf),
+    }
+}
+
+fn sample_pdf(o: Vec3, pdf: &dyn Pdf) -> (Ray, f64) {
+    let scattered = Ray::new(o, pdf.generate());
+    let value = pdf.value(scattered.direction());
+    (scattered, value)
+}
+
+// `f` gives the scattering weight towards a light and the media the shadow ray starts in
+fn sample_punctual<'m>(
+    scene: &'m Scene,
+    p: &Vec3,
+    wavelengths: Option<Vec3>,
+    f: impl Fn(&Ray) -> (Vec3, MediumStack<'m>),
+) -> Vec3 {
+    scene
+        .punctual_lights
+        .iter()
+        .filter_map(|light| light.sample(p))
+        .map(|sample| {
+            let shadow_ray = Ray::new(*p, sample.direction).with_wavelengths(wavelengths);
+            let (f, stack) = f(&shadow_ray);
+            if f.near_zero() {
+                return Vec3::zero();
+            }
+            f * transmittance(scene, &shadow_ray, sample.distance, stack)
+                * at_wavelengths(&sample.radiance, &shadow_ray)
+        })
+        .sum()
+}
+
+// visibility along a shadow ray, attenuated by the media it passes through
+fn transmittance<'m>(scene: &'m Scene, r: &Ray, t_max: f64, mut stack: MediumStack<'m>) -> Vec3 {
+    let mut transmittance = vec3(1, 1, 1);
+    let mut t_min = 0.001;
 
-    match scatter.kind() {
-        ScatterKind::Diffuse { pdf } => {
-            let (scattered, pdf) = if let Some(lights) = lights {
-                let light_pdf = HitablePdf::new(rec.p, lights);
-                let mixture = MixturePdf::new(light_pdf, pdf.as_ref());
-
-                let scattered = Ray::new(rec.p, mixture.generate());
-                (scattered, mixture.value(scattered.direction()))
-            } else {
-                let mixture = pdf.as_ref();
-                let scattered = Ray::new(rec.p, mixture.generate());
-                (scattered, mixture.value(scattered.direction()))
-            };
-
-            emitted
-                + scatter.attenuation()
-                    * rec.material.scattering_pdf(r, &rec, &scattered)
-                    * color(&scattered, world, lights, background, depth - 1)
-                    / pdf
+    loop {
+        let rec = scene.world.hit(r, t_min, t_max - 0.001);
+        let t_end = rec.as_ref().map_or(t_max, |rec| rec.t);
+        if let Some(medium) = stack.medium() {
+            transmittance *= medium.transmittance(r, t_min, t_end);
         }
-        ScatterKind::Specular { specular_ray } => {
-            scatter.attenuation() * color(&specular_ray, world, lights, background, depth - 1)
+
+        match rec {
+            None => return transmittance,
+            Some(rec) if rec.material.is_interface() || stack.is_false_intersection(&rec) => {
+                stack = stack.cross(&rec, r.direction(), r.direction());
+                t_min = rec.t + 0.001;
+            }
+            Some(_) => return Vec3::zero(),
         }
     }
 }
@@ -110,14 +264,16 @@ pub fn four_spheres(nx: usize, ny: usize) -> Scene {
         ),
         Sphere::new(vec3(1., 0., -1.), 0.5, Metal::new(vec3(0.8, 0.6, 0.2), 0.3)),
         Sphere::new(vec3(-1., 0., -1.), 0.5, Dielectric::new(1.5)),
-        Sphere::new(vec3(-1., 0., -1.), -0.45, Dielectric::new(1.5)),
+        // air bubble in the glass
+        Sphere::new(vec3(-1., 0., -1.), 0.45, Dielectric::new(1.).priority(1)),
     ]);
 
     Scene {
         world,
         lights: None,
+        punctual_lights: Vec::new(),
         cam,
-        background: vec3(0.70, 0.80, 1.00),
+        background: Box::new(vec3(0.70, 0.80, 1.00)),
     }
 }
 
@@ -172,7 +328,7 @@ pub fn cornell_box(nx: usize, ny: usize) -> Scene {
 
     let light_rect = XzRect::new(vec2(213., 343.), vec2(227., 332.), 554., light);
 
-    let lights = light_rect.clone().boxed();
+    let lights = light_rect.clone().flip_face().boxed();
 
     let world = Box::new([
         YzRect::new(vec2(0., 555.), vec2(0., 555.), 555., green).boxed() as Box<dyn Hitable>,
@@ -194,8 +350,9 @@ pub fn cornell_box(nx: usize, ny: usize) -> Scene {
     Scene {
         world,
         lights: Some(lights),
+        punctual_lights: Vec::new(),
         cam,
-        background: Vec3::zero(),
+        background: Box::new(Vec3::zero()),
     }
 }
 
@@ -218,9 +375,12 @@ pub fn cornell_specular(nx: usize, ny: usize) -> Scene {
 
     let light_rect = XzRect::new(vec2(213., 343.), vec2(227., 332.), 554., light).shared();
     let sphere = Sphere::new(vec3(190, 90, 190), 90., glass).shared();
-    let cube = Sphere::new(vec3(430., 90., 250.), 90., aluminum)
-        .shared();
-    let lights = Box::new([light_rect.clone(), sphere.clone(), cube.clone()]);
+    let cube = Sphere::new(vec3(430., 90., 250.), 90., aluminum).shared();
+    let lights = Box::new([
+        light_rect.clone().flip_face().shared(),
+        sphere.clone(),
+        cube.clone(),
+    ]);
 
     let world = Box::new([
         YzRect::new(vec2(0., 555.), vec2(0., 555.), 555., green).shared(),
@@ -236,8 +396,9 @@ pub fn cornell_specular(nx: usize, ny: usize) -> Scene {
     Scene {
         world,
         lights: Some(lights),
+        punctual_lights: Vec::new(),
         cam,
-        background: Vec3::zero(),
+        background: Box::new(Vec3::zero()),
     }
 }
 
@@ -278,15 +439,33 @@ pub fn cornell_smoke() -> Box<dyn Hitable> {
 pub struct Scene {
     pub world: Box<dyn Hitable>,
     pub lights: Option<Box<dyn Hitable>>,
+    pub punctual_lights: Vec<Box<dyn PunctualLight>>,
     pub cam: Camera,
-    pub background: Vec3,
+    pub background: Box<dyn Environment>,
 }
 
 impl Scene {
     pub fn fill_buf(&self, nx: usize, ny: usize, ns: usize) -> Vec<[u8; 4]> {
+        self.render(nx, ny, ns, |ray| color(ray, self, 50, &MediumStack::new()))
+    }
+
+    // every path carries three wavelengths instead of rgb, which brings out dispersion
+    pub fn fill_buf_spectral(&self, nx: usize, ny: usize, ns: usize) -> Vec<[u8; 4]> {
+        self.render(nx, ny, ns, |ray| {
+            let lambdas = sample_wavelengths(random());
+            let ray = ray.with_wavelengths(Some(lambdas));
+            spectral_to_rgb(&color(&ray, self, 50, &MediumStack::new()), &lambdas)
+        })
+    }
+
+    fn render(
+        &self,
+        nx: usize,
+        ny: usize,
+        ns: usize,
+        sample: impl Fn(&Ray) -> Vec3 + Sync,
+    ) -> Vec<[u8; 4]> {
         let cam = &self.cam;
-        let world = &self.world;
-        let lights = &self.lights;
 
         let n = ny * nx;
 
@@ -304,31 +483,21 @@ impl Scene {
                 let j = ny - n / nx;
 
                 let mut rng = thread_rng();
+                // with more samples each of them only needs to cover part of the pixel
+                let pixel =
+                    vec2(1. / nx as f64, 1. / ny as f64) * f64::max(0.125, 1. / (ns as f64).sqrt());
 
                 let col: Vec3 = (0..ns)
                     .map(|_s| {
                         let u = (i as f64 + rng.gen::<f64>()) / nx as f64;
                         let v = (j as f64 + rng.gen::<f64>()) / ny as f64;
 
-                        let ray = cam.get_ray(u, v);
-                        color(
-                            &ray,
-                            world.as_ref(),
-                            lights.as_deref(),
-                            &self.background,
-                            50,
-                        )
+                        let ray = cam.get_ray(u, v, pixel);
+                        sample(&ray)
                     })
                     .sum::<Vec3>();
 
-                let col = col.map(|c| if c.is_nan() { 0.0 } else { c });
-                let col = col.map(|c| f64::sqrt(c / ns as f64));
-
-                let ir = (255.99 * col[0]) as u8;
-                let ig = (255.99 * col[1]) as u8;
-                let ib = (255.99 * col[2]) as u8;
-
-                [ir, ig, ib, 0]
+                to_rgba(col / ns as f64)
             })
             .collect();
 
@@ -336,3 +505,14 @@ impl Scene {
         vec
     }
 }
+
+fn to_rgba(col: Vec3) -> [u8; 4] {
+    let col = col.map(|c| if c.is_nan() { 0.0 } else { c });
+    let col = col.map(f64::sqrt);
+
+    let ir = (255.99 * col[0]) as u8;
+    let ig = (255.99 * col[1]) as u8;
+    let ib = (255.99 * col[2]) as u8;
+
+    [ir, ig, ib, 0]
+}
@@ -0,0 +1,462 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use rand::random;
+
+use crate::{
+    hit::{Aabb, HitRecord, Hitable, Ray, SurfaceSample},
+    ies::IesProfile,
+    math::{cross, dot, random_unit_vector, vec2, vec3, Distribution2D, Onb, Vec2, Vec3},
+    spectrum::{luminance, NITS_PER_UNIT},
+};
+
+// solid angle density of `area_random`, summed over every crossing of the surface along `v`
+pub fn area_pdf_value(shape: &(impl Hitable + ?Sized), o: &Vec3, v: &Vec3) -> f64 {
+    let area = shape.area();
+    if area <= 0. {
+        return 0.;
+    }
+
+    let r = Ray::new(*o, *v);
+    let mut t_min = 0.001;
+    let mut pdf = 0.;
+    while let Some(rec) = shape.hit(&r, t_min, f64::INFINITY) {
+        let distance_squared = rec.t * rec.t * v.length_squared();
+        let cosine = dot(v, &rec.normal).abs() / v.length();
+        if cosine > 0. {
+            pdf += distance_squared / (cosine * area);
+        }
+        t_min = rec.t + 0.0001;
+    }
+    pdf
+}
+
+pub fn area_random(shape: &(impl Hitable + ?Sized), o: &Vec3) -> Vec3 {
+    match shape.sample_surface() {
+        Some(sample) => sample.p - o,
+        None => vec3(1, 0, 0),
+    }
+}
+
+// An emitter sampled by how bright its texture is where, for screens, signs and other lights
+// whose emission varies a lot over the surface. The shape has to map texture coordinates to
+// points, see `Hitable::sample_uv`.
+pub struct TexturedLight<T> {
+    distribution: Distribution2D,
+    inner: T,
+}
+
+impl<T> TexturedLight<T>
+where
+    T: Hitable,
+{
+    // tabulates the emission along the normal over `resolution` squared cells of texture space
+    pub fn new(inner: T, resolution: usize) -> Self {
+        let n = resolution.max(1);
+        let weights: Vec<f64> = (0..n)
+            .flat_map(|j| (0..n).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let uv = vec2((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+                match inner.sample_uv(uv) {
+                    Some(sample) if sample.pdf > 0. => {
+                        luminance(&sample.emitted(&sample.normal)).max(0.) / sample.pdf
+                    }
+                    _ => 0.,
+                }
+            })
+            .collect();
+
+        Self {
+            distribution: Distribution2D::new(&weights, n, n),
+            inner,
+        }
+    }
+
+    // area density at a hit, the texture space density over the area each unit of it covers
+    fn area_pdf(&self, rec: &HitRecord) -> f64 {
+        let jacobian = cross(&rec.dpdu, &rec.dpdv).length();
+        let uv = vec2(rec.uv.u().rem_euclid(1.), rec.uv.v().rem_euclid(1.));
+        if jacobian > 0. {
+            self.distribution.pdf(uv) / jacobian
+        } else {
+            0.
+        }
+    }
+}
+
+impl<T> Hitable for TexturedLight<T>
+where
+    T: Hitable,
+{
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.inner.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.inner.bounding_box()
+    }
+
+    // summed over every crossing, like `area_pdf_value`
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        let r = Ray::new(*o, *v);
+        let mut t_min = 0.001;
+        let mut pdf = 0.;
+        while let Some(rec) = self.inner.hit(&r, t_min, f64::INFINITY) {
+            let distance_squared = rec.t * rec.t * v.length_squared();
+            let cosine = dot(v, &rec.normal).abs() / v.length();
+            if cosine > 0. {
+                pdf += self.area_pdf(&rec) * distance_squared / cosine;
+            }
+            t_min = rec.t + 0.0001;
+        }
+        pdf
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        area_random(self, o)
+    }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        let (uv, pdf) = self
+            .distribution
+            .sample(vec2(random::<f64>(), random::<f64>()));
+        self.inner.sample_uv(uv).map(|mut sample| {
+            sample.pdf *= pdf;
+            sample
+        })
+    }
+
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        self.inner.sample_uv(uv)
+    }
+
+    fn area(&self) -> f64 {
+        self.inner.area()
+    }
+
+    fn power(&self) -> Vec3 {
+        self.inner.power()
+    }
+}
+
+pub struct LightSample {
+    pub direction: Vec3,
+    pub distance: f64,
+    pub radiance: Vec3,
+}
+
+// a photon leaving a light, carrying `power` in flux over the density it was emitted with
+pub struct PhotonSample {
+    pub ray: Ray,
+    pub power: Vec3,
+}
+
+// lights that no ray can hit, they only contribute through explicit sampling
+pub trait PunctualLight: Send + Sync {
+    fn sample(&self, p: &Vec3) -> Option<LightSample>;
+
+    // total flux into a scene within `bounds`
+    fn power(&self, bounds: &Aabb) -> Vec3;
+
+    // a photon into a scene within `bounds`, for photon mapping
+    fn emit(&self, bounds: &Aabb) -> Option<PhotonSample>;
+}
+
+pub struct PointLight {
+    position: Vec3,
+    intensity: Vec3,
+    radius: f64,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, intensity: Vec3) -> Self {
+        Self {
+            position,
+            intensity,
+            radius: 0.,
+        }
+    }
+
+    // jitters the light position over a sphere for soft shadows
+    pub fn radius(self, radius: f64) -> Self {
+        Self { radius, ..self }
+    }
+
+    // total flux in lm, keeps the colour of the intensity. See `NITS_PER_UNIT` for how it maps
+    // to scene units.
+    pub fn lumens(self, lumens: f64) -> Self {
+        let y = luminance(&self.intensity);
+        let color = if y > 0. {
+            self.intensity / y
+        } else {
+            vec3(1, 1, 1)
+        };
+        Self {
+            intensity: lumens / (4. * PI) / NITS_PER_UNIT * color,
+            ..self
+        }
+    }
+}
+
+impl PointLight {
+    fn jittered(&self) -> Vec3 {
+        self.position + self.radius * random_unit_vector()
+    }
+}
+
+impl PunctualLight for PointLight {
+    fn sample(&self, p: &Vec3) -> Option<LightSample> {
+        let to_light = self.jittered() - p;
+        let distance = to_light.length();
+
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            radiance: self.intensity / (distance * distance),
+        })
+    }
+
+    fn power(&self, _bounds: &Aabb) -> Vec3 {
+        4. * PI * self.intensity
+    }
+
+    fn emit(&self, bounds: &Aabb) -> Option<PhotonSample> {
+        Some(PhotonSample {
+            ray: Ray::new(self.jittered(), random_unit_vector()),
+            power: self.power(bounds),
+        })
+    }
+}
+
+pub struct SpotLight {
+    light: PointLight,
+    direction: Vec3,
+    cos_total: f64,
+    cos_falloff_start: f64,
+}
+
+impl SpotLight {
+    // `angle` is the half angle of the cone, `falloff` how much of it is spent fading out, in degrees
+    pub fn new(position: Vec3, target: Vec3, intensity: Vec3, angle: f64, falloff: f64) -> Self {
+        Self {
+            light: PointLight::new(position, intensity),
+            direction: (target - position).normalize(),
+            cos_total: angle.to_radians().cos(),
+            cos_falloff_start: (angle - falloff).max(0.).to_radians().cos(),
+        }
+    }
+
+    pub fn radius(self, radius: f64) -> Self {
+        Self {
+            light: self.light.radius(radius),
+            ..self
+        }
+    }
+
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta < self.cos_total {
+            0.
+        } else if cos_theta >= self.cos_falloff_start {
+            1.
+        } else {
+            let t = (cos_theta - self.cos_total) / (self.cos_falloff_start - self.cos_total);
+            t * t * (3. - 2. * t)
+        }
+    }
+}
+
+impl PunctualLight for SpotLight {
+    fn sample(&self, p: &Vec3) -> Option<LightSample> {
+        let mut sample = self.light.sample(p)?;
+        let falloff = self.falloff(dot(&-sample.direction, &self.direction));
+        if falloff <= 0. {
+            return None;
+        }
+        sample.radiance = falloff * sample.radiance;
+        Some(sample)
+    }
+
+    // the fading edge counted as half lit
+    fn power(&self, _bounds: &Aabb) -> Vec3 {
+        let cos_edge = 0.5 * (self.cos_total + self.cos_falloff_start);
+        2. * PI * (1. - cos_edge) * self.light.intensity
+    }
+
+    fn emit(&self, _bounds: &Aabb) -> Option<PhotonSample> {
+        let local = random_in_cone(self.cos_total);
+        let falloff = self.falloff(local.z());
+        if falloff <= 0. {
+            return None;
+        }
+        let solid_angle = 2. * PI * (1. - self.cos_total);
+        Some(PhotonSample {
+            ray: Ray::new(
+                self.light.jittered(),
+                Onb::build_from(&self.direction).local(&local),
+            ),
+            power: falloff * solid_angle * self.light.intensity,
+        })
+    }
+}
+
+// point light shining like a measured luminaire
+pub struct IesLight {
+    light: PointLight,
+    profile: Arc<IesProfile>,
+    nadir: Vec3,
+    c0: Vec3,
+    color: Vec3,
+    scale: f64,
+}
+
+impl IesLight {
+    // at the profile's own candela values, hanging straight down with its C0 plane along x
+    pub fn new(position: Vec3, profile: impl Into<Arc<IesProfile>>) -> Self {
+        Self {
+            light: PointLight::new(position, vec3(1, 1, 1)),
+            profile: profile.into(),
+            nadir: vec3(0, -1, 0),
+            c0: vec3(1, 0, 0),
+            color: vec3(1, 1, 1),
+            scale: 1.,
+        }
+    }
+
+    // `nadir` is where the luminaire points, `c0` the direction of its C0 plane
+    pub fn orient(self, nadir: Vec3, c0: Vec3) -> Self {
+        let nadir = nadir.normalize();
+        Self {
+            nadir,
+            c0: (c0 - dot(&c0, &nadir) * nadir).normalize(),
+            ..self
+        }
+    }
+
+    // normalized to unit luminance
+    pub fn color(self, color: Vec3) -> Self {
+        let y = luminance(&color);
+        Self {
+            color: if y > 0. { color / y } else { Vec3::zero() },
+            ..self
+        }
+    }
+
+    // scales the profile to the given total flux in lm
+    pub fn lumens(self, lumens: f64) -> Self {
+        let total = self.profile.lumens();
+        Self {
+            scale: if total > 0. { lumens / total } else { 0. },
+            ..self
+        }
+    }
+
+    pub fn radius(self, radius: f64) -> Self {
+        Self {
+            light: self.light.radius(radius),
+            ..self
+        }
+    }
+
+    // in scene units, towards the world space `direction`
+    fn intensity(&self, direction: &Vec3) -> Vec3 {
+        let local = vec3(
+            dot(direction, &self.c0),
+            dot(direction, &cross(&self.nadir, &self.c0)),
+            dot(direction, &self.nadir),
+        );
+        self.scale * self.profile.candela(&local) / NITS_PER_UNIT * self.color
+    }
+}
+
+impl PunctualLight for IesLight {
+    fn sample(&self, p: &Vec3) -> Option<LightSample> {
+        let mut sample = self.light.sample(p)?;
+        let intensity = self.intensity(&-sample.direction);
+        if intensity.near_zero() {
+            return None;
+        }
+        sample.radiance = intensity * sample.radiance;
+        Some(sample)
+    }
+
+    fn power(&self, _bounds: &Aabb) -> Vec3 {
+        self.scale * self.profile.lumens() / NITS_PER_UNIT * self.color
+    }
+
+    // uniformly over the sphere, profiles can be too lopsided for anything simpler to fit
+    fn emit(&self, _bounds: &Aabb) -> Option<PhotonSample> {
+        let direction = random_unit_vector();
+        let intensity = self.intensity(&direction);
+        if intensity.near_zero() {
+            return None;
+        }
+        Some(PhotonSample {
+            ray: Ray::new(self.light.jittered(), direction),
+            power: 4. * PI * intensity,
+        })
+    }
+}
+
+pub struct DirectionalLight {
+    uvw: Onb,
+    irradiance: Vec3,
+    cos_max: f64,
+}
+
+impl DirectionalLight {
+    // `direction` points towards the light, `angular_diameter` is in degrees
+    pub fn new(direction: Vec3, irradiance: Vec3, angular_diameter: f64) -> Self {
+        Self {
+            uvw: Onb::build_from(&direction),
+            irradiance,
+            cos_max: (0.5 * angular_diameter).to_radians().cos(),
+        }
+    }
+}
+
+impl PunctualLight for DirectionalLight {
+    fn sample(&self, _p: &Vec3) -> Option<LightSample> {
+        let direction = if self.cos_max < 1. {
+            self.uvw.local(&random_in_cone(self.cos_max))
+        } else {
+            *self.uvw.w()
+        };
+
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            radiance: self.irradiance,
+        })
+    }
+
+    // through the disk the scene's bounding sphere casts
+    fn power(&self, bounds: &Aabb) -> Vec3 {
+        let radius = 0.5 * (bounds.max() - bounds.min()).length();
+        PI * radius * radius * self.irradiance
+    }
+
+    fn emit(&self, bounds: &Aabb) -> Option<PhotonSample> {
+        let center = 0.5 * (bounds.min() + bounds.max());
+        let radius = 0.5 * (bounds.max() - bounds.min()).length();
+        let disk = vec2::random_in_unit_sphere();
+        let (u, v, w) = (self.uvw.u(), self.uvw.v(), self.uvw.w());
+        let origin = center + radius * (*w + disk.u() * *u + disk.v() * *v);
+        let direction = self.sample(&center)?.direction;
+
+        Some(PhotonSample {
+            ray: Ray::new(origin, -direction),
+            power: self.power(bounds),
+        })
+    }
+}
+
+pub fn random_in_cone(cos_max: f64) -> Vec3 {
+    let r1: f64 = random();
+    let r2: f64 = random();
+    let z = 1. - r2 * (1. - cos_max);
+
+    let phi = 2. * PI * r1;
+    let (sin, cos) = phi.sin_cos();
+    let sq = f64::sqrt(1. - z * z);
+
+    vec3(cos * sq, sin * sq, z)
+}
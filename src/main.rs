@@ -1,6 +1,7 @@
-use std::thread;
+use std::{path::PathBuf, process, thread};
 
 use clap::Clap;
+use raytrace2::environment::ImageEnvironment;
 use winit::{
     dpi::PhysicalSize,
     event::{Event, WindowEvent},
@@ -16,10 +17,38 @@ struct Opts {
     nx: usize,
     #[clap(default_value = "500")]
     ny: usize,
+    /// render with progressive photon mapping, using `ns` passes of this many photons
+    #[clap(long)]
+    photons: Option<usize>,
+    /// lat-long .hdr or .exr map that replaces the scene background
+    #[clap(long)]
+    environment: Option<PathBuf>,
+    /// trace wavelengths instead of rgb, for dispersion
+    #[clap(long)]
+    spectral: bool,
 }
 
 fn main() {
-    let Opts { ns, nx, ny } = dbg!(Opts::parse());
+    let Opts {
+        ns,
+        nx,
+        ny,
+        photons,
+        environment,
+        spectral,
+    } = dbg!(Opts::parse());
+
+    // photons are only emitted from the scene's lights, an environment map would light the
+    // camera rays directly but nothing else
+    if photons.is_some() && environment.is_some() {
+        eprintln!("--photons does not support --environment lighting");
+        process::exit(2);
+    }
+    // photons carry rgb power, there is no spectral photon pass
+    if photons.is_some() && spectral {
+        eprintln!("--photons does not support --spectral rendering");
+        process::exit(2);
+    }
 
     rayon::ThreadPoolBuilder::new()
         .num_threads(8)
@@ -40,10 +69,17 @@ fn main() {
         .build()
         .unwrap();
 
-    let scene = raytrace2::cornell_specular(nx, ny);
+    let mut scene = raytrace2::cornell_specular(nx, ny);
+    if let Some(path) = environment {
+        scene.background = Box::new(ImageEnvironment::open(path).unwrap());
+    }
 
     thread::spawn(move || {
-        let buffer = scene.fill_buf(nx, ny, ns);
+        let buffer = match photons {
+            Some(photons) => scene.fill_buf_sppm(nx, ny, ns, photons),
+            None if spectral => scene.fill_buf_spectral(nx, ny, ns),
+            None => scene.fill_buf(nx, ny, ns),
+        };
         event_proxy.send_event(buffer).unwrap();
     });
 
@@ -3,10 +3,13 @@ use std::{f64::consts::PI, sync::Arc};
 use rand::random;
 
 use crate::{
-    hit::{HitRecord, Material, Ray, Scatter},
-    math::{dot, random_in_unit_sphere, reflect, refract, vec3, Vec2, Vec3},
-    pdf::CosinePdf,
+    hit::{HitRecord, MatPtr, Material, Ray, Scatter, ScatterKind},
+    ies::IesProfile,
+    math::{cross, dot, random_in_unit_sphere, reflect, refract, vec2, vec3, Onb, Vec2, Vec3},
+    pdf::{CosinePdf, MixturePdf},
+    spectrum::{flat_efficacy, terminate_secondary, upsample, Spectrum, NITS_PER_UNIT},
     texture::{self, TexPtr, Texture},
+    volume::{HenyeyGreenstein, HomogeneousMedium, Medium},
 };
 
 pub struct Lambertian {
@@ -30,7 +33,7 @@ impl Lambertian {
 
 impl Material for Lambertian {
     fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
-        let attenuation = self.albedo.value(rec.uv, &rec.p);
+        let attenuation = self.albedo.at_hit(rec);
         let pdf = CosinePdf::new(&rec.normal);
         Some(Scatter::new_diffuse(attenuation, Box::new(pdf)))
     }
@@ -62,18 +65,93 @@ impl Metal {
 impl Material for Metal {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
         let reflected = reflect(&r_in.direction().normalize(), &rec.normal);
-        let scattered = Ray::new(rec.p, reflected + self.fuzz * random_in_unit_sphere());
+        let scattered = Ray::new(rec.p, reflected + self.fuzz * random_in_unit_sphere())
+            .with_differentials(rec.reflect_differentials(r_in));
         Some(Scatter::new_specular(scattered, self.albedo))
     }
 }
 
+// wavelength used for the index of refraction outside of spectral mode, the sodium d line
+const D_LINE: f64 = 587.6;
+
+// index of refraction as a function of wavelength, coefficients take wavelengths in µm
+#[derive(Debug, Clone, Copy)]
+pub enum Ior {
+    Constant(f64),
+    // n = a + b / λ²
+    Cauchy { a: f64, b: f64 },
+    // n² = 1 + Σ b λ² / (λ² - c)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Ior {
+    pub fn bk7() -> Self {
+        Ior::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    pub fn fused_silica() -> Self {
+        Ior::Sellmeier {
+            b: [0.6961663, 0.4079426, 0.8974794],
+            c: [0.00467914826, 0.0135120631, 97.9340025],
+        }
+    }
+
+    pub fn diamond() -> Self {
+        Ior::Sellmeier {
+            b: [4.3356, 0.3306, 0.],
+            c: [0.011236, 0.030625, 0.],
+        }
+    }
+
+    pub fn at(&self, lambda: f64) -> f64 {
+        let l2 = (lambda * 1e-3).powi(2);
+        match self {
+            Ior::Constant(n) => *n,
+            Ior::Cauchy { a, b } => a + b / l2,
+            Ior::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1. + sum).sqrt()
+            }
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+}
+
 pub struct Dielectric {
-    ir: f64,
+    ior: Ior,
+    interior: Option<Arc<dyn Medium>>,
+    priority: u32,
 }
 
 impl Dielectric {
     pub fn new(ir: f64) -> Self {
-        Self { ir }
+        Self::with_ior(Ior::Constant(ir))
+    }
+
+    // dispersive indices split light into its wavelengths in spectral mode
+    pub fn with_ior(ior: Ior) -> Self {
+        Self {
+            ior,
+            interior: None,
+            priority: 0,
+        }
+    }
+
+    pub fn priority(self, priority: u32) -> Self {
+        Self { priority, ..self }
+    }
+
+    pub fn with_interior(self, interior: impl Medium + 'static) -> Self {
+        Self {
+            interior: Some(Arc::new(interior)),
+            ..self
+        }
     }
 
     fn schlick(cosine: f64, ref_idx: f64) -> f64 {
@@ -86,10 +164,17 @@ impl Dielectric {
 impl Material for Dielectric {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
         let attenuation = vec3(1, 1, 1);
+        let (ir, wavelengths) = match r_in.wavelengths() {
+            Some(lambdas) if self.ior.is_dispersive() => (
+                self.ior.at(lambdas.x()),
+                Some(terminate_secondary(&lambdas)),
+            ),
+            _ => (self.ior.at(D_LINE), None),
+        };
         let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
+            rec.outer_ior / ir
         } else {
-            self.ir
+            ir / rec.outer_ior
         };
 
         let unit_direction = r_in.direction().normalize();
@@ -97,27 +182,327 @@ impl Material for Dielectric {
         let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction = if cannot_refract || Self::schlick(cos_theta, refraction_ratio) > random() {
-            reflect(&unit_direction, &rec.normal)
-        } else {
-            refract(&unit_direction, &rec.normal, refraction_ratio)
-        };
+        let (direction, differentials) =
+            if cannot_refract || Self::schlick(cos_theta, refraction_ratio) > random() {
+                (
+                    reflect(&unit_direction, &rec.normal),
+                    rec.reflect_differentials(r_in),
+                )
+            } else {
+                (
+                    refract(&unit_direction, &rec.normal, refraction_ratio),
+                    rec.refract_differentials(r_in, refraction_ratio),
+                )
+            };
 
         Some(Scatter::new_specular(
-            Ray::new(rec.p, direction),
+            Ray::new(rec.p, direction)
+                .with_wavelengths(wavelengths)
+                .with_differentials(differentials),
             attenuation,
         ))
     }
+
+    fn interior(&self) -> Option<&dyn Medium> {
+        self.interior.as_deref()
+    }
+
+    fn ior(&self) -> Option<f64> {
+        Some(self.ior.at(D_LINE))
+    }
+
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+// translucent solid such as skin, wax or marble. Light refracts in through a smooth dielectric
+// surface, random walks through a scattering interior and leaves somewhere else. The shape has to
+// be closed.
+pub struct Subsurface {
+    sigma_a: Vec3,
+    sigma_s: Vec3,
+    ior: f64,
+    anisotropy: f64,
+    priority: u32,
+    surface: Dielectric,
+}
+
+impl Subsurface {
+    // `color` is what a thick slab looks like under diffuse light, `mean_free_path` how far light
+    // gets into it per channel, in scene units. Red travelling further than blue gives skin its
+    // glow.
+    pub fn new(color: Vec3, mean_free_path: Vec3) -> Self {
+        let sigma_t = mean_free_path.map(|d| 1. / d.max(1e-8));
+        let albedo = color.map(single_scattering_albedo);
+        Self::with_coefficients(sigma_t - albedo * sigma_t, albedo * sigma_t)
+    }
+
+    // absorption and scattering coefficients per unit of distance
+    pub fn with_coefficients(sigma_a: Vec3, sigma_s: Vec3) -> Self {
+        Self::assemble(sigma_a, sigma_s, 1.4, 0., 0)
+    }
+
+    // Jensen et al.'s measurements, in mm⁻¹ scaled to scenes where one unit is `units_per_mm`
+    pub fn marble(units_per_mm: f64) -> Self {
+        let per_unit = 1. / units_per_mm;
+        Self::with_coefficients(
+            per_unit * vec3(0.0021, 0.0041, 0.0071),
+            per_unit * vec3(2.19, 2.62, 3.),
+        )
+        .ior(1.5)
+    }
+
+    pub fn skin(units_per_mm: f64) -> Self {
+        let per_unit = 1. / units_per_mm;
+        Self::with_coefficients(
+            per_unit * vec3(0.032, 0.17, 0.48),
+            per_unit * vec3(0.74, 0.88, 1.01),
+        )
+        .ior(1.3)
+    }
+
+    pub fn ior(self, ior: f64) -> Self {
+        Self::assemble(
+            self.sigma_a,
+            self.sigma_s,
+            ior,
+            self.anisotropy,
+            self.priority,
+        )
+    }
+
+    // henyey greenstein asymmetry of the interior, skin scatters mostly forward at around 0.8
+    pub fn anisotropy(self, g: f64) -> Self {
+        Self::assemble(self.sigma_a, self.sigma_s, self.ior, g, self.priority)
+    }
+
+    pub fn priority(self, priority: u32) -> Self {
+        Self::assemble(
+            self.sigma_a,
+            self.sigma_s,
+            self.ior,
+            self.anisotropy,
+            priority,
+        )
+    }
+
+    fn assemble(sigma_a: Vec3, sigma_s: Vec3, ior: f64, anisotropy: f64, priority: u32) -> Self {
+        let interior =
+            HomogeneousMedium::new(sigma_a, sigma_s).phase(HenyeyGreenstein::new(anisotropy));
+        Self {
+            sigma_a,
+            sigma_s,
+            ior,
+            anisotropy,
+            priority,
+            surface: Dielectric::new(ior)
+                .with_interior(interior)
+                .priority(priority),
+        }
+    }
+}
+
+// albedo of a single scattering event that makes a semi-infinite medium reflect `color` in
+// total, the fit from Chiang et al.'s "Practical and Controllable Subsurface Scattering"
+fn single_scattering_albedo(color: f64) -> f64 {
+    let a = color.clamp(0., 0.999);
+    let s = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+    1. - s * s
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
+        self.surface.scatter(r_in, rec)
+    }
+
+    fn interior(&self) -> Option<&dyn Medium> {
+        self.surface.interior()
+    }
+
+    fn ior(&self) -> Option<f64> {
+        self.surface.ior()
+    }
+
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Strength {
+    // radiance in scene units
+    Radiance(f64),
+    // luminous flux out of an emitter with the given surface area
+    Lumens { lumens: f64, area: f64 },
+    // radiant flux out of an emitter with the given surface area
+    Watts { watts: f64, area: f64 },
 }
 
 pub struct DiffuseLight {
     texture: Arc<dyn Texture>,
+    strength: Strength,
+    tint: Vec3,
+    // emission spectrum and the factor bringing it to unit luminance
+    spectrum: Option<(Spectrum, f64)>,
+    // lumens per radiant watt of the emission
+    efficacy: f64,
+    falloff: f64,
+    profile: Option<Arc<IesProfile>>,
+    two_sided: bool,
 }
 
 impl DiffuseLight {
     pub fn new(texture: impl TexPtr) -> Self {
         Self {
             texture: texture.into(),
+            strength: Strength::Radiance(1.),
+            tint: vec3(1, 1, 1),
+            spectrum: None,
+            efficacy: flat_efficacy(),
+            falloff: 0.,
+            profile: None,
+            two_sided: false,
+        }
+    }
+
+    pub fn intensity(self, intensity: f64) -> Self {
+        Self {
+            strength: Strength::Radiance(intensity),
+            ..self
+        }
+    }
+
+    // The physical units assume a texture and tint of unit luminance, see `NITS_PER_UNIT` for
+    // how they map to scene units. Luminance in cd/m² along the normal.
+    pub fn nits(self, nits: f64) -> Self {
+        self.intensity(nits / NITS_PER_UNIT)
+    }
+
+    // total flux in lm, `area` is that of the shape the light is put on
+    pub fn lumens(self, lumens: f64, area: f64) -> Self {
+        Self {
+            strength: Strength::Lumens { lumens, area },
+            ..self
+        }
+    }
+
+    // total radiant flux, converted to lumens by the efficacy of the light's spectrum, or of a
+    // flat one without
+    pub fn watts(self, watts: f64, area: f64) -> Self {
+        Self {
+            strength: Strength::Watts { watts, area },
+            ..self
+        }
+    }
+
+    pub fn temperature(self, kelvin: f64) -> Self {
+        self.spectrum(Spectrum::Blackbody(kelvin))
+    }
+
+    // colours the light by the spectrum, normalized to unit luminance. Spectral mode emits the
+    // spectrum itself, rgb mode its colour.
+    pub fn spectrum(self, spectrum: Spectrum) -> Self {
+        Self {
+            tint: spectrum.to_rgb(),
+            spectrum: Some((spectrum.clone(), spectrum.normalization())),
+            efficacy: spectrum.efficacy(),
+            ..self
+        }
+    }
+
+    pub fn two_sided(self) -> Self {
+        Self {
+            two_sided: true,
+            ..self
+        }
+    }
+
+    // radiance falls off with cos^exponent away from the surface normal
+    pub fn spot(self, exponent: f64) -> Self {
+        Self {
+            falloff: exponent,
+            ..self
+        }
+    }
+
+    // shapes the emission like a measured luminaire, with its nadir along the outward normal
+    // and its C0 plane along the shape's u tangent. Radiance is divided by the cosine, which
+    // makes the intensity of the whole emitter follow the profile. Replaces `spot`, the back of
+    // a two sided light gets the profile's upper half.
+    pub fn ies(self, profile: impl Into<Arc<IesProfile>>) -> Self {
+        Self {
+            profile: Some(profile.into()),
+            ..self
+        }
+    }
+
+    fn scale(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        if !rec.front_face && !self.two_sided {
+            return 0.;
+        }
+
+        self.radiance() * self.distribution(r_in, rec)
+    }
+
+    // radiance along the normal, in scene units
+    fn radiance(&self) -> f64 {
+        let from_lumens = |lumens: f64, area: f64| {
+            let solid_angle = self.solid_angle();
+            if area > 0. && solid_angle > 0. {
+                lumens / (area * solid_angle) / NITS_PER_UNIT
+            } else {
+                0.
+            }
+        };
+
+        match self.strength {
+            Strength::Radiance(radiance) => radiance,
+            Strength::Lumens { lumens, area } => from_lumens(lumens, area),
+            Strength::Watts { watts, area } => from_lumens(watts * self.efficacy, area),
+        }
+    }
+
+    // radiance relative to the one along the normal
+    fn distribution(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        let direction = -r_in.direction().normalize();
+        match &self.profile {
+            Some(profile) if profile.peak() > 0. => {
+                let n = rec.outward_normal();
+                let cosine = dot(&direction, &n);
+                let tangent = if rec.dpdu.near_zero() {
+                    *Onb::build_from(&n).u()
+                } else {
+                    rec.dpdu
+                };
+                let x = (tangent - dot(&tangent, &n) * n).normalize();
+                let y = cross(&n, &x);
+                let local = vec3(dot(&direction, &x), dot(&direction, &y), cosine);
+                profile.candela(&local) / (profile.peak() * cosine.abs().max(0.01))
+            }
+            Some(_) => 0.,
+            None if self.falloff > 0. => dot(&direction, &rec.normal).abs().powf(self.falloff),
+            None => 1.,
+        }
+    }
+
+    // integral of the distribution times the cosine over the directions the light emits into,
+    // flux per radiance and area
+    fn solid_angle(&self) -> f64 {
+        match &self.profile {
+            Some(profile) if profile.peak() > 0. => {
+                let back = if self.two_sided {
+                    profile.hemisphere_lumens(true)
+                } else {
+                    0.
+                };
+                (profile.hemisphere_lumens(false) + back) / profile.peak()
+            }
+            Some(_) => 0.,
+            None => {
+                let sides = if self.two_sided { 2. } else { 1. };
+                sides * 2. * PI / (self.falloff + 2.)
+            }
         }
     }
 }
@@ -127,11 +512,463 @@ impl Material for DiffuseLight {
         None
     }
 
-    fn emitted(&self, _r_in: &Ray, rec: &HitRecord, uv: Vec2, p: &Vec3) -> Vec3 {
-        if rec.front_face {
-            self.texture.value(uv, p)
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, uv: Vec2, p: &Vec3) -> Vec3 {
+        let texture = self.texture.at_hit(&HitRecord { uv, p: *p, ..*rec });
+        self.scale(r_in, rec) * self.tint * texture
+    }
+
+    fn emitted_spectral(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        uv: Vec2,
+        p: &Vec3,
+        lambdas: &Vec3,
+    ) -> Vec3 {
+        let texture = upsample(
+            &self.texture.at_hit(&HitRecord { uv, p: *p, ..*rec }),
+            lambdas,
+        );
+        let spectrum = match &self.spectrum {
+            Some((spectrum, normalization)) => *normalization * spectrum.values(lambdas),
+            None => upsample(&self.tint, lambdas),
+        };
+        self.scale(r_in, rec) * spectrum * texture
+    }
+
+    // textures are assumed to be roughly uniform, so the centre value stands in for the average
+    fn power(&self, area: f64) -> Vec3 {
+        let radiance =
+            self.radiance() * self.tint * self.texture.value(vec2(0.5, 0.5), &Vec3::zero());
+
+        area * self.solid_angle() * radiance
+    }
+
+    fn is_two_sided(&self) -> bool {
+        self.two_sided
+    }
+}
+
+// step in texture space for differentiating bump maps
+const BUMP_DELTA: f64 = 0.0005;
+
+enum Perturbation {
+    Height {
+        texture: Arc<dyn Texture>,
+        scale: f64,
+    },
+    NormalMap(Arc<dyn Texture>),
+}
+
+// shades another material with a perturbed normal. Needs a shape with tangents, anything else
+// is left flat.
+pub struct Bumped {
+    inner: Arc<dyn Material>,
+    perturbation: Perturbation,
+}
+
+impl Bumped {
+    // bump map, the surface appears offset along its normal by `scale` times the texture
+    pub fn new(inner: impl MatPtr, height: impl TexPtr, scale: f64) -> Self {
+        Self {
+            inner: inner.into(),
+            perturbation: Perturbation::Height {
+                texture: height.into(),
+                scale,
+            },
+        }
+    }
+
+    // tangent space normal map, colours in [0, 1] encode directions in [-1, 1] with blue along
+    // the normal
+    pub fn normal_map(inner: impl MatPtr, map: impl TexPtr) -> Self {
+        Self {
+            inner: inner.into(),
+            perturbation: Perturbation::NormalMap(map.into()),
+        }
+    }
+
+    // on the outside, like the outward normal
+    fn shading_normal(&self, rec: &HitRecord) -> Option<Vec3> {
+        if rec.dpdu.near_zero() || rec.dpdv.near_zero() {
+            return None;
+        }
+        let n = rec.outward_normal();
+
+        let normal = match &self.perturbation {
+            Perturbation::Height { texture, scale } => {
+                let height = |uv: Vec2, p: Vec3| scale * texture.value(uv, &p).mean();
+                let h = height(rec.uv, rec.p);
+                let du = height(rec.uv + vec2(BUMP_DELTA, 0.), rec.p + BUMP_DELTA * rec.dpdu);
+                let dv = height(rec.uv + vec2(0., BUMP_DELTA), rec.p + BUMP_DELTA * rec.dpdv);
+
+                let dpdu = rec.dpdu + (du - h) / BUMP_DELTA * n;
+                let dpdv = rec.dpdv + (dv - h) / BUMP_DELTA * n;
+                let normal = cross(&dpdu, &dpdv).normalize();
+                if dot(&normal, &n) < 0. {
+                    -normal
+                } else {
+                    normal
+                }
+            }
+            Perturbation::NormalMap(map) => {
+                let m = 2. * map.value(rec.uv, &rec.p) - 1.;
+                let t = (rec.dpdu - dot(&rec.dpdu, &n) * n).normalize();
+                let b = cross(&n, &t);
+                let b = if dot(&b, &rec.dpdv) < 0. { -b } else { b };
+                (m.x() * t + m.y() * b + m.z() * n).normalize()
+            }
+        };
+
+        Some(normal)
+    }
+
+    // the hit as the inner material sees it. A shading normal turned away from the viewer would
+    // shade the back of the surface, the geometric one stays in that case.
+    fn shade<'m>(&self, r_in: &Ray, rec: &HitRecord<'m>) -> HitRecord<'m> {
+        let normal = match self.shading_normal(rec) {
+            Some(normal) if rec.front_face => normal,
+            Some(normal) => -normal,
+            None => rec.normal,
+        };
+        let normal = if dot(&normal, r_in.direction()) < 0. {
+            normal
         } else {
-            Vec3::zero()
+            rec.normal
+        };
+
+        HitRecord { normal, ..*rec }
+    }
+}
+
+impl Material for Bumped {
+    // specular rays the shading normal sends to the wrong side of the actual surface are
+    // mirrored back, otherwise they would leak through it
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
+        let shaded = self.shade(r_in, rec);
+        let scatter = self.inner.scatter(r_in, &shaded)?;
+
+        if let ScatterKind::Specular { specular_ray } = scatter.kind() {
+            let direction = *specular_ray.direction();
+            let intended = dot(&direction, &shaded.normal) > 0.;
+            let actual = dot(&direction, &rec.normal) > 0.;
+            if intended != actual {
+                let mirrored = direction - 2. * dot(&direction, &rec.normal) * rec.normal;
+                let ray = Ray::new(*specular_ray.origin(), mirrored)
+                    .with_wavelengths(specular_ray.wavelengths())
+                    .with_differentials(rec.reflect_differentials(r_in));
+                return Some(Scatter::new_specular(ray, *scatter.attenuation()));
+            }
+        }
+
+        Some(scatter)
+    }
+
+    // directions below the actual surface get nothing, whatever the shading normal says
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        if dot(scattered.direction(), &rec.normal) <= 0. {
+            return 0.;
+        }
+        self.inner
+            .scattering_pdf(r_in, &self.shade(r_in, rec), scattered)
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, uv: Vec2, p: &Vec3) -> Vec3 {
+        self.inner.emitted(r_in, &self.shade(r_in, rec), uv, p)
+    }
+
+    fn emitted_spectral(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        uv: Vec2,
+        p: &Vec3,
+        lambdas: &Vec3,
+    ) -> Vec3 {
+        self.inner
+            .emitted_spectral(r_in, &self.shade(r_in, rec), uv, p, lambdas)
+    }
+
+    fn power(&self, area: f64) -> Vec3 {
+        self.inner.power(area)
+    }
+
+    fn is_two_sided(&self) -> bool {
+        self.inner.is_two_sided()
+    }
+
+    fn is_volumetric(&self) -> bool {
+        self.inner.is_volumetric()
+    }
+
+    fn interior(&self) -> Option<&dyn Medium> {
+        self.inner.interior()
+    }
+
+    fn is_interface(&self) -> bool {
+        self.inner.is_interface()
+    }
+
+    fn ior(&self) -> Option<f64> {
+        self.inner.ior()
+    }
+
+    fn priority(&self) -> u32 {
+        self.inner.priority()
+    }
+}
+
+// salts of the materials choosing lobes with `lobe_selection`
+const MIX_SALT: u64 = 1;
+const LAYERED_SALT: u64 = 2;
+
+// uniform in [0, 1), and the same whenever the integrator asks about the same ray and hit. A
+// material choosing between lobes with it makes the same choice in `scatter` as in
+// `scattering_pdf`, even when the photon pass asks again much later. Each kind of material
+// salts it differently, so a mix of layered materials doesn't tie the two choices together.
+fn lobe_selection(salt: u64, r_in: &Ray, rec: &HitRecord) -> f64 {
+    let d = r_in.direction();
+    let words = [d.x(), d.y(), d.z(), rec.p.x(), rec.p.y(), rec.p.z()];
+    let mut h = words.iter().fold(salt, |h, w| {
+        (h ^ w.to_bits()).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    });
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^= h >> 31;
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// `a` where the factor is 0, `b` where it is 1. In between, diffuse lobes are blended, sampled
+// with the factor as probability and weighted by it in `scattering_pdf`. A specular lobe is
+// picked with the factor as probability, so that part converges over many samples.
+pub struct MixMaterial {
+    a: Arc<dyn Material>,
+    b: Arc<dyn Material>,
+    factor: Arc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(a: impl MatPtr, b: impl MatPtr, factor: impl TexPtr) -> Self {
+        Self {
+            a: a.into(),
+            b: b.into(),
+            factor: factor.into(),
         }
     }
+
+    fn weight(&self, rec: &HitRecord) -> f64 {
+        self.factor.at_hit(rec).mean().clamp(0., 1.)
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
+        let t = self.weight(rec);
+        let pick_b = lobe_selection(MIX_SALT, r_in, rec) < t;
+        let (picked, other) = if pick_b {
+            (&self.b, &self.a)
+        } else {
+            (&self.a, &self.b)
+        };
+        let picked = match picked.scatter(r_in, rec)?.into_parts() {
+            (attenuation, ScatterKind::Diffuse { pdf }) => (attenuation, pdf),
+            (attenuation, ScatterKind::Specular { specular_ray }) => {
+                return Some(Scatter::new_specular(specular_ray, attenuation))
+            }
+        };
+
+        // `scattering_pdf` weighs each diffuse lobe by its factor. When the other lobe isn't
+        // diffuse this one is only scattered off by chance, and its attenuation makes up for it.
+        let other = match other.scatter(r_in, rec).map(Scatter::into_parts) {
+            Some((attenuation, ScatterKind::Diffuse { pdf })) => (attenuation, pdf),
+            _ => {
+                let chance = if pick_b { t } else { 1. - t };
+                return Some(Scatter::new_diffuse(picked.0 / chance, picked.1));
+            }
+        };
+        let ((attenuation_a, pdf_a), (attenuation_b, pdf_b)) = if pick_b {
+            (other, picked)
+        } else {
+            (picked, other)
+        };
+        Some(Scatter::new_diffuse(
+            (1. - t) * attenuation_a + t * attenuation_b,
+            Box::new(MixturePdf::weighted(pdf_a, pdf_b, t)),
+        ))
+    }
+
+    // specular lobes add nothing here, they scatter in a single direction
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let t = self.weight(rec);
+        (1. - t) * self.a.scattering_pdf(r_in, rec, scattered)
+            + t * self.b.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, uv: Vec2, p: &Vec3) -> Vec3 {
+        let t = self.weight(&HitRecord { uv, p: *p, ..*rec });
+        (1. - t) * self.a.emitted(r_in, rec, uv, p) + t * self.b.emitted(r_in, rec, uv, p)
+    }
+
+    fn emitted_spectral(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        uv: Vec2,
+        p: &Vec3,
+        lambdas: &Vec3,
+    ) -> Vec3 {
+        let t = self.weight(&HitRecord { uv, p: *p, ..*rec });
+        (1. - t) * self.a.emitted_spectral(r_in, rec, uv, p, lambdas)
+            + t * self.b.emitted_spectral(r_in, rec, uv, p, lambdas)
+    }
+
+    // like a light's texture, the factor is assumed to be roughly uniform
+    fn power(&self, area: f64) -> Vec3 {
+        let t = self
+            .factor
+            .value(vec2(0.5, 0.5), &Vec3::zero())
+            .mean()
+            .clamp(0., 1.);
+        (1. - t) * self.a.power(area) + t * self.b.power(area)
+    }
+
+    fn is_two_sided(&self) -> bool {
+        self.a.is_two_sided() || self.b.is_two_sided()
+    }
+
+    // a surface bounds a single medium, the first material decides which
+    fn interior(&self) -> Option<&dyn Medium> {
+        self.a.interior()
+    }
+
+    fn ior(&self) -> Option<f64> {
+        self.a.ior()
+    }
+
+    fn priority(&self) -> u32 {
+        self.a.priority()
+    }
+}
+
+// a clear dielectric coat over another material, like varnish or the clearcoat on car paint.
+// Light reflects off the coat by the fresnel term, what gets through is scattered by the base
+// and dimmed again on its way out. The coat is thin, rays reach the base unbent.
+pub struct Layered {
+    base: Arc<dyn Material>,
+    ior: f64,
+    tint: Vec3,
+    roughness: f64,
+}
+
+impl Layered {
+    pub fn new(base: impl MatPtr, ior: f64) -> Self {
+        Self {
+            base: base.into(),
+            ior,
+            tint: vec3(1, 1, 1),
+            roughness: 0.,
+        }
+    }
+
+    // absorption of the coat, applied once on the way in and once on the way out
+    pub fn tint(self, tint: Vec3) -> Self {
+        Self { tint, ..self }
+    }
+
+    // fuzz of the coat's reflection, like `Metal`'s
+    pub fn roughness(self, roughness: f64) -> Self {
+        Self {
+            roughness: roughness.min(1.),
+            ..self
+        }
+    }
+
+    fn reflectance(&self, rec: &HitRecord, direction: &Vec3) -> f64 {
+        let cosine = dot(&direction.normalize(), &rec.normal).abs().min(1.);
+        let r0 = ((rec.outer_ior - self.ior) / (rec.outer_ior + self.ior)).powi(2);
+        r0 + (1. - r0) * (1. - cosine).powi(5)
+    }
+
+    // the coat is on the outside, hits from within see the base alone
+    fn reflects(&self, r_in: &Ray, rec: &HitRecord) -> bool {
+        rec.front_face
+            && lobe_selection(LAYERED_SALT, r_in, rec) < self.reflectance(rec, r_in.direction())
+    }
+}
+
+impl Material for Layered {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
+        if self.reflects(r_in, rec) {
+            let reflected = reflect(&r_in.direction().normalize(), &rec.normal);
+            let mut direction = reflected + self.roughness * random_in_unit_sphere();
+            if dot(&direction, &rec.normal) <= 0. {
+                direction = reflected;
+            }
+            let ray = Ray::new(rec.p, direction)
+                .with_wavelengths(r_in.wavelengths())
+                .with_differentials(rec.reflect_differentials(r_in));
+            return Some(Scatter::new_specular(ray, vec3(1, 1, 1)));
+        }
+
+        let scatter = self.base.scatter(r_in, rec)?;
+        if !rec.front_face {
+            return Some(scatter);
+        }
+        let absorption = self.tint * self.tint;
+        match scatter.kind() {
+            // the way out of a diffuse bounce is only known in `scattering_pdf`
+            ScatterKind::Diffuse { .. } => Some(scatter.attenuated(absorption)),
+            ScatterKind::Specular { specular_ray } => {
+                let exit = 1. - self.reflectance(rec, specular_ray.direction());
+                Some(scatter.attenuated(exit * absorption))
+            }
+        }
+    }
+
+    // zero for the coat's mirror, otherwise the base's, dimmed by the coat on the way out
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        if self.reflects(r_in, rec) {
+            return 0.;
+        }
+        let pdf = self.base.scattering_pdf(r_in, rec, scattered);
+        if !rec.front_face {
+            return pdf;
+        }
+        pdf * (1. - self.reflectance(rec, scattered.direction()))
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, uv: Vec2, p: &Vec3) -> Vec3 {
+        self.base.emitted(r_in, rec, uv, p)
+    }
+
+    fn emitted_spectral(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        uv: Vec2,
+        p: &Vec3,
+        lambdas: &Vec3,
+    ) -> Vec3 {
+        self.base.emitted_spectral(r_in, rec, uv, p, lambdas)
+    }
+
+    fn power(&self, area: f64) -> Vec3 {
+        self.base.power(area)
+    }
+
+    fn is_two_sided(&self) -> bool {
+        self.base.is_two_sided()
+    }
+
+    fn interior(&self) -> Option<&dyn Medium> {
+        self.base.interior()
+    }
+
+    fn ior(&self) -> Option<f64> {
+        self.base.ior()
+    }
+
+    fn priority(&self) -> u32 {
+        self.base.priority()
+    }
 }
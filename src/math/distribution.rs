@@ -0,0 +1,110 @@
+use super::{vec2, Vec2};
+
+// piecewise constant density over [0, 1)
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(mut func: Vec<f64>) -> Self {
+        // a stray nan or infinity from an image or texture would poison the whole cdf
+        func.iter_mut()
+            .filter(|f| !f.is_finite() || **f < 0.)
+            .for_each(|f| *f = 0.);
+
+        let n = func.len();
+        let mut cdf = vec![0.; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f64;
+        }
+
+        let integral = cdf[n];
+        if integral > 0. {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        } else {
+            cdf.iter_mut()
+                .enumerate()
+                .for_each(|(i, c)| *c = i as f64 / n as f64);
+        }
+
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    pub fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.func.is_empty()
+    }
+
+    // returns the sampled position, its density and the index of the segment it fell into
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let offset = match self.cdf.binary_search_by(|c| c.total_cmp(&u)) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        }
+        .min(self.len() - 1);
+
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0. {
+            du /= width;
+        }
+
+        let x = (offset as f64 + du) / self.len() as f64;
+        (x, self.pdf(x), offset)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        if self.integral <= 0. {
+            return 1.;
+        }
+        let offset = ((x * self.len() as f64) as usize).min(self.len() - 1);
+        self.func[offset] / self.integral
+    }
+}
+
+// piecewise constant density over [0, 1)^2, `func` holds `nv` rows of `nu` values
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], nu: usize, nv: usize) -> Self {
+        let conditional: Vec<_> = func
+            .chunks(nu)
+            .take(nv)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|c| c.integral()).collect());
+
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    pub fn sample(&self, u: Vec2) -> (Vec2, f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u.y());
+        let (u, pdf_u, _) = self.conditional[row].sample(u.x());
+        (vec2(u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, uv: Vec2) -> f64 {
+        let rows = self.conditional.len();
+        let row = ((uv.v() * rows as f64) as usize).min(rows - 1);
+        self.marginal.pdf(uv.v()) * self.conditional[row].pdf(uv.u())
+    }
+}
@@ -10,6 +10,11 @@ pub use vec3_impl::*;
 mod onb;
 pub use onb::Onb;
 
+mod distribution;
+pub use distribution::{Distribution1D, Distribution2D};
+
+pub mod poly;
+
 pub fn random_cosine_direction() -> Vec3 {
     let r1: f64 = random();
     let r2: f64 = random();
@@ -1,4 +1,4 @@
-use super::{cross, vec3, Vec3};
+use super::{cross, dot, vec3, Vec3};
 
 pub struct Onb {
     axis: [Vec3; 3],
@@ -33,4 +33,9 @@ impl Onb {
     pub fn local(&self, a: &Vec3) -> Vec3 {
         a.x() * self.u() + a.y() * self.v() + a.z() * self.w()
     }
+
+    // inverse of `local`
+    pub fn to_local(&self, a: &Vec3) -> Vec3 {
+        vec3(dot(a, self.u()), dot(a, self.v()), dot(a, self.w()))
+    }
 }
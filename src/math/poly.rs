@@ -0,0 +1,139 @@
+// Closed form real roots of low degree polynomials, after Schwarze's "Cubic and Quartic Roots"
+// in Graphics Gems I. Coefficients are given lowest power first, roots come back unsorted.
+
+const EPSILON: f64 = 1e-9;
+
+fn is_zero(x: f64) -> bool {
+    x.abs() < EPSILON
+}
+
+// c[0] + c[1] x + c[2] x^2
+pub fn solve_quadratic(c: [f64; 3]) -> Vec<f64> {
+    if is_zero(c[2]) {
+        if is_zero(c[1]) {
+            return Vec::new();
+        }
+        return vec![-c[0] / c[1]];
+    }
+
+    let p = c[1] / (2. * c[2]);
+    let q = c[0] / c[2];
+    let d = p * p - q;
+
+    if is_zero(d) {
+        vec![-p]
+    } else if d < 0. {
+        Vec::new()
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![sqrt_d - p, -sqrt_d - p]
+    }
+}
+
+// c[0] + c[1] x + c[2] x^2 + c[3] x^3
+pub fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    if is_zero(c[3]) {
+        return solve_quadratic([c[0], c[1], c[2]]);
+    }
+
+    // normal form x^3 + Ax^2 + Bx + C, then substitute x = y - A/3 to drop the quadratic term
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let cc = c[0] / c[3];
+
+    let sq_a = a * a;
+    let p = 1. / 3. * (-1. / 3. * sq_a + b);
+    let q = 1. / 2. * (2. / 27. * a * sq_a - 1. / 3. * a * b + cc);
+
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+
+    let mut roots = if is_zero(d) {
+        if is_zero(q) {
+            vec![0.]
+        } else {
+            let u = (-q).cbrt();
+            vec![2. * u, -u]
+        }
+    } else if d < 0. {
+        let phi = 1. / 3. * (-q / (-cb_p).sqrt()).clamp(-1., 1.).acos();
+        let t = 2. * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + std::f64::consts::PI / 3.).cos(),
+            -t * (phi - std::f64::consts::PI / 3.).cos(),
+        ]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    roots.iter_mut().for_each(|root| *root -= 1. / 3. * a);
+    roots
+}
+
+// c[0] + c[1] x + c[2] x^2 + c[3] x^3 + c[4] x^4
+pub fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    if is_zero(c[4]) {
+        return solve_cubic([c[0], c[1], c[2], c[3]]);
+    }
+
+    // normal form x^4 + Ax^3 + Bx^2 + Cx + D, then substitute x = y - A/4
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    let sq_a = a * a;
+    let p = -3. / 8. * sq_a + b;
+    let q = 1. / 8. * sq_a * a - 1. / 2. * a * b + cc;
+    let r = -3. / 256. * sq_a * sq_a + 1. / 16. * sq_a * b - 1. / 4. * a * cc + d;
+
+    let mut roots = if is_zero(r) {
+        // y (y^3 + py + q) = 0
+        let mut roots = solve_cubic([q, p, 0., 1.]);
+        roots.push(0.);
+        roots
+    } else {
+        // one real root of the resolvent cubic splits the quartic into two quadratics
+        let z = solve_cubic([1. / 2. * r * p - 1. / 8. * q * q, -r, -1. / 2. * p, 1.])[0];
+
+        let u = z * z - r;
+        let v = 2. * z - p;
+        let u = if is_zero(u) {
+            0.
+        } else if u > 0. {
+            u.sqrt()
+        } else {
+            return Vec::new();
+        };
+        let v = if is_zero(v) {
+            0.
+        } else if v > 0. {
+            v.sqrt()
+        } else {
+            return Vec::new();
+        };
+
+        let v = if q < 0. { -v } else { v };
+        let mut roots = solve_quadratic([z - u, v, 1.]);
+        roots.extend(solve_quadratic([z + u, -v, 1.]));
+        roots
+    };
+
+    roots.iter_mut().for_each(|root| *root -= 1. / 4. * a);
+
+    // the closed form loses precision quickly, a few newton steps on the original polynomial
+    // bring the roots back
+    for root in &mut roots {
+        for _ in 0..2 {
+            let x = *root;
+            let f = (((c[4] * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0];
+            let df = ((4. * c[4] * x + 3. * c[3]) * x + 2. * c[2]) * x + c[1];
+            if df != 0. {
+                *root = x - f / df;
+            }
+        }
+    }
+    roots
+}
@@ -65,6 +65,10 @@ impl Vec3 {
         self[0] * self[1] * self[2]
     }
 
+    pub fn mean(&self) -> f64 {
+        (self[0] + self[1] + self[2]) / 3.
+    }
+
     pub fn map(&self, f: impl FnMut(f64) -> f64) -> Self {
         Self { e: self.e.map(f) }
     }
@@ -134,6 +138,10 @@ mod r {
             }
         }
     }
+
+    pub fn random_unit_vector() -> Vec3 {
+        random_in_unit_sphere().normalize()
+    }
 }
 pub use r::*;
 
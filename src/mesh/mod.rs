@@ -0,0 +1,138 @@
+// Polygon meshes, refined at load time and tessellated into triangles under a bvh
+
+use std::sync::Arc;
+
+use crate::{
+    containers::BvhNode,
+    hit::MatPtr,
+    math::{cross, vec2, Vec2, Vec3},
+    objects::triangle::Triangle,
+    texture::{TexPtr, Texture},
+    transform::HitableExt,
+};
+
+mod obj;
+
+mod subdivision;
+pub use subdivision::*;
+
+pub struct Face {
+    // indices into the mesh positions, counter-clockwise seen from the front
+    pub vertices: Vec<usize>,
+    // texture coordinates at each corner
+    pub uvs: Option<Vec<Vec2>>,
+}
+
+impl Face {
+    pub fn new(vertices: Vec<usize>) -> Self {
+        Self {
+            vertices,
+            uvs: None,
+        }
+    }
+
+    pub fn uvs(self, uvs: Vec<Vec2>) -> Self {
+        Self {
+            uvs: Some(uvs),
+            ..self
+        }
+    }
+
+    fn uv(&self, corner: usize) -> Vec2 {
+        self.uvs.as_ref().map_or(vec2(0., 0.), |uvs| uvs[corner])
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let next = self.vertices.iter().cycle().skip(1);
+        self.vertices.iter().copied().zip(next.copied())
+    }
+
+    // fan around the first corner, as corner indices
+    fn triangles(&self) -> impl Iterator<Item = [usize; 3]> {
+        (1..self.vertices.len().saturating_sub(1)).map(|i| [0, i, i + 1])
+    }
+}
+
+pub struct Mesh {
+    positions: Vec<Vec3>,
+    faces: Vec<Face>,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Vec3>, faces: Vec<Face>) -> Self {
+        Self { positions, faces }
+    }
+
+    pub fn positions(&self) -> &[Vec3] {
+        &self.positions
+    }
+
+    pub fn faces(&self) -> &[Face] {
+        &self.faces
+    }
+
+    // area weighted average of the normals of the faces around each vertex
+    pub fn vertex_normals(&self) -> Vec<Vec3> {
+        let mut normals = vec![Vec3::zero(); self.positions.len()];
+        for face in &self.faces {
+            for [a, b, c] in face.triangles() {
+                let (a, b, c) = (face.vertices[a], face.vertices[b], face.vertices[c]);
+                let p = &self.positions;
+                let normal = cross(&(p[b] - p[a]), &(p[c] - p[a]));
+                for v in [a, b, c].iter() {
+                    normals[*v] += normal;
+                }
+            }
+        }
+        normals
+            .into_iter()
+            .map(|n| if n.near_zero() { n } else { n.normalize() })
+            .collect()
+    }
+
+    // moves every vertex along its normal by `scale` times the texture value. Vertices on a uv
+    // seam take the coordinates of the first face that uses them.
+    pub fn displace(self, height: impl TexPtr, scale: f64) -> Self {
+        let height: Arc<dyn Texture> = height.into();
+        let normals = self.vertex_normals();
+
+        let mut uvs = vec![None; self.positions.len()];
+        for face in &self.faces {
+            for (corner, v) in face.vertices.iter().enumerate() {
+                uvs[*v].get_or_insert_with(|| face.uv(corner));
+            }
+        }
+
+        let positions = self
+            .positions
+            .iter()
+            .zip(normals)
+            .zip(uvs)
+            .map(|((p, normal), uv)| {
+                let uv = uv.unwrap_or_else(|| vec2(0., 0.));
+                p + scale * height.value(uv, p).mean() * normal
+            })
+            .collect();
+
+        Self { positions, ..self }
+    }
+
+    // splits the faces into smoothly shaded triangles under a bvh
+    pub fn build(self, material: impl MatPtr) -> BvhNode {
+        let material = material.into();
+        let normals = self.vertex_normals();
+
+        let mut triangles = Vec::new();
+        for face in &self.faces {
+            for corners in face.triangles() {
+                let v = corners.map(|c| face.vertices[c]);
+                let triangle = Triangle::new(v.map(|v| self.positions[v]), material.clone())
+                    .smooth(v.map(|v| normals[v]))
+                    .uvs(corners.map(|c| face.uv(c)));
+                triangles.push(triangle.boxed());
+            }
+        }
+
+        BvhNode::new(triangles)
+    }
+}
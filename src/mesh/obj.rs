@@ -0,0 +1,98 @@
+// Wavefront OBJ, only positions, texture coordinates and faces are read. Normals are
+// recomputed after refinement anyway.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use crate::math::{vec2, vec3, Vec2};
+
+use super::{Face, Mesh};
+
+impl Mesh {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read(reader: impl BufRead) -> io::Result<Self> {
+        let mut positions = Vec::new();
+        let mut uvs: Vec<Vec2> = Vec::new();
+        let mut faces = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let [x, y, z] = parse_floats(tokens)?;
+                    positions.push(vec3(x, y, z));
+                }
+                Some("vt") => {
+                    let [u, v] = parse_floats(tokens)?;
+                    uvs.push(vec2(u, v));
+                }
+                Some("f") => {
+                    let mut vertices = Vec::new();
+                    let mut corner_uvs = Vec::new();
+                    for corner in tokens {
+                        let mut indices = corner.split('/');
+                        let v = indices.next().unwrap_or("");
+                        vertices.push(resolve(v, positions.len())?);
+                        match indices.next() {
+                            Some(vt) if !vt.is_empty() => {
+                                corner_uvs.push(uvs[resolve(vt, uvs.len())?]);
+                            }
+                            _ => {}
+                        }
+                    }
+                    if vertices.len() < 3 {
+                        return Err(invalid_data("face with less than three corners"));
+                    }
+
+                    let face = if corner_uvs.len() == vertices.len() {
+                        Face::new(vertices).uvs(corner_uvs)
+                    } else {
+                        Face::new(vertices)
+                    };
+                    faces.push(face);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Mesh::new(positions, faces))
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_floats<'a, const N: usize>(
+    mut tokens: impl Iterator<Item = &'a str>,
+) -> io::Result<[f64; N]> {
+    let mut values = [0.; N];
+    for value in &mut values {
+        *value = tokens
+            .next()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| invalid_data("malformed number"))?;
+    }
+    Ok(values)
+}
+
+// one based index, negative ones count back from the last element read so far
+fn resolve(index: &str, len: usize) -> io::Result<usize> {
+    let index: isize = index.parse().map_err(|_| invalid_data("malformed index"))?;
+    let resolved = if index < 0 {
+        len as isize + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved as usize >= len {
+        return Err(invalid_data("index out of range"));
+    }
+    Ok(resolved as usize)
+}
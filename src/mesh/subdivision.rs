@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use crate::math::{Vec2, Vec3};
+
+use super::{Face, Mesh};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Subdivision {
+    // any polygons, every level turns them into quads
+    CatmullClark,
+    // triangles, other polygons are split into fans first
+    Loop,
+}
+
+impl Mesh {
+    pub fn subdivide(self, scheme: Subdivision, levels: usize) -> Self {
+        (0..levels).fold(self, |mesh, _| match scheme {
+            Subdivision::CatmullClark => catmull_clark(&mesh),
+            Subdivision::Loop => loop_subdivision(&mesh.triangulated()),
+        })
+    }
+
+    fn triangulated(self) -> Self {
+        let faces = self
+            .faces
+            .iter()
+            .flat_map(|face| {
+                face.triangles().map(move |corners| Face {
+                    vertices: corners.iter().map(|c| face.vertices[*c]).collect(),
+                    uvs: face
+                        .uvs
+                        .as_ref()
+                        .map(|uvs| corners.iter().map(|c| uvs[*c]).collect()),
+                })
+            })
+            .collect();
+        Self { faces, ..self }
+    }
+}
+
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+// faces on each edge, an edge with a single face lies on the boundary
+fn edge_faces(faces: &[Face]) -> HashMap<(usize, usize), Vec<usize>> {
+    let mut edges: HashMap<_, Vec<usize>> = HashMap::new();
+    for (i, face) in faces.iter().enumerate() {
+        for (a, b) in face.edges() {
+            edges.entry(edge(a, b)).or_default().push(i);
+        }
+    }
+    edges
+}
+
+// neighbours of every vertex, along with the ones across boundary edges
+fn vertex_neighbours(
+    n: usize,
+    edges: &HashMap<(usize, usize), Vec<usize>>,
+) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+    let mut all = vec![Vec::new(); n];
+    let mut boundary = vec![Vec::new(); n];
+    for (&(a, b), faces) in edges {
+        all[a].push(b);
+        all[b].push(a);
+        if faces.len() == 1 {
+            boundary[a].push(b);
+            boundary[b].push(a);
+        }
+    }
+    (all, boundary)
+}
+
+// boundaries are refined as cubic b-spline curves. Corners, where a vertex only has its two
+// boundary edges or more than two of them meet, stay put.
+fn boundary_vertex(positions: &[Vec3], v: usize, neighbours: &[usize], boundary: &[usize]) -> Vec3 {
+    match boundary {
+        [a, b] if neighbours.len() > 2 => {
+            0.75 * positions[v] + 0.125 * (positions[*a] + positions[*b])
+        }
+        _ => positions[v],
+    }
+}
+
+fn catmull_clark(mesh: &Mesh) -> Mesh {
+    let positions = &mesh.positions;
+    let n = positions.len();
+    let edges = edge_faces(&mesh.faces);
+    let (neighbours, boundary) = vertex_neighbours(n, &edges);
+
+    let face_points: Vec<Vec3> = mesh
+        .faces
+        .iter()
+        .map(|face| {
+            let sum: Vec3 = face.vertices.iter().map(|v| positions[*v]).sum();
+            sum / face.vertices.len() as f64
+        })
+        .collect();
+
+    let mut vertex_faces = vec![Vec::new(); n];
+    for (i, face) in mesh.faces.iter().enumerate() {
+        for v in &face.vertices {
+            vertex_faces[*v].push(i);
+        }
+    }
+
+    // original vertices first, then one point per face, then one per edge
+    let mut points: Vec<Vec3> = (0..n)
+        .map(|v| {
+            let p = positions[v];
+            if !boundary[v].is_empty() {
+                return boundary_vertex(positions, v, &neighbours[v], &boundary[v]);
+            }
+            if vertex_faces[v].is_empty() {
+                return p;
+            }
+
+            let valence = vertex_faces[v].len() as f64;
+            let q: Vec3 = vertex_faces[v].iter().map(|f| face_points[*f]).sum();
+            let r: Vec3 = neighbours[v].iter().map(|w| (p + positions[*w]) / 2.).sum();
+            let q = q / valence;
+            let r = r / neighbours[v].len() as f64;
+            (q + 2. * r + (valence - 3.) * p) / valence
+        })
+        .collect();
+    points.extend(face_points.iter().copied());
+
+    let mut edge_points = HashMap::new();
+    for face in &mesh.faces {
+        for (a, b) in face.edges() {
+            edge_points.entry(edge(a, b)).or_insert_with(|| {
+                let adjacent = &edges[&edge(a, b)];
+                let point = match adjacent[..] {
+                    [f, g] => (positions[a] + positions[b] + face_points[f] + face_points[g]) / 4.,
+                    _ => (positions[a] + positions[b]) / 2.,
+                };
+                points.push(point);
+                points.len() - 1
+            });
+        }
+    }
+
+    let mut faces = Vec::new();
+    for (f, face) in mesh.faces.iter().enumerate() {
+        let count = face.vertices.len();
+        let center_uv = face
+            .uvs
+            .as_ref()
+            .map(|uvs| uvs.iter().copied().sum::<Vec2>() / count as f64);
+
+        for i in 0..count {
+            let (prev, next) = ((i + count - 1) % count, (i + 1) % count);
+            let (vp, v, vn) = (face.vertices[prev], face.vertices[i], face.vertices[next]);
+            let vertices = vec![
+                v,
+                edge_points[&edge(v, vn)],
+                n + f,
+                edge_points[&edge(vp, v)],
+            ];
+            let uvs = face.uvs.as_ref().zip(center_uv).map(|(uvs, center)| {
+                vec![
+                    uvs[i],
+                    (uvs[i] + uvs[next]) / 2.,
+                    center,
+                    (uvs[prev] + uvs[i]) / 2.,
+                ]
+            });
+            faces.push(Face { vertices, uvs });
+        }
+    }
+
+    Mesh::new(points, faces)
+}
+
+fn loop_subdivision(mesh: &Mesh) -> Mesh {
+    let positions = &mesh.positions;
+    let n = positions.len();
+    let edges = edge_faces(&mesh.faces);
+    let (neighbours, boundary) = vertex_neighbours(n, &edges);
+
+    let mut points: Vec<Vec3> = (0..n)
+        .map(|v| {
+            let p = positions[v];
+            if !boundary[v].is_empty() {
+                return boundary_vertex(positions, v, &neighbours[v], &boundary[v]);
+            }
+            if neighbours[v].is_empty() {
+                return p;
+            }
+
+            let valence = neighbours[v].len();
+            let beta = if valence == 3 {
+                3. / 16.
+            } else {
+                3. / (8. * valence as f64)
+            };
+            let sum: Vec3 = neighbours[v].iter().map(|w| positions[*w]).sum();
+            (1. - valence as f64 * beta) * p + beta * sum
+        })
+        .collect();
+
+    // the vertex across an edge from each of its faces
+    let opposite = |face: usize, a: usize, b: usize| {
+        *mesh.faces[face]
+            .vertices
+            .iter()
+            .find(|v| **v != a && **v != b)
+            .unwrap()
+    };
+
+    let mut edge_points = HashMap::new();
+    for face in &mesh.faces {
+        for (a, b) in face.edges() {
+            edge_points.entry(edge(a, b)).or_insert_with(|| {
+                let adjacent = &edges[&edge(a, b)];
+                let point = match adjacent[..] {
+                    [f, g] => {
+                        let (c, d) = (opposite(f, a, b), opposite(g, a, b));
+                        0.375 * (positions[a] + positions[b])
+                            + 0.125 * (positions[c] + positions[d])
+                    }
+                    _ => (positions[a] + positions[b]) / 2.,
+                };
+                points.push(point);
+                points.len() - 1
+            });
+        }
+    }
+
+    let mut faces = Vec::new();
+    for face in &mesh.faces {
+        let [a, b, c] = [face.vertices[0], face.vertices[1], face.vertices[2]];
+        let (ab, bc, ca) = (
+            edge_points[&edge(a, b)],
+            edge_points[&edge(b, c)],
+            edge_points[&edge(c, a)],
+        );
+
+        let mid = |uvs: &Vec<Vec2>, i: usize, j: usize| (uvs[i] + uvs[j]) / 2.;
+        let corner_uvs = face.uvs.as_ref().map(|uvs| {
+            [
+                vec![uvs[0], mid(uvs, 0, 1), mid(uvs, 2, 0)],
+                vec![mid(uvs, 0, 1), uvs[1], mid(uvs, 1, 2)],
+                vec![mid(uvs, 2, 0), mid(uvs, 1, 2), uvs[2]],
+                vec![mid(uvs, 0, 1), mid(uvs, 1, 2), mid(uvs, 2, 0)],
+            ]
+        });
+
+        let triangles = [[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]];
+        for (i, vertices) in triangles.iter().enumerate() {
+            faces.push(Face {
+                vertices: vertices.to_vec(),
+                uvs: corner_uvs.as_ref().map(|uvs| uvs[i].clone()),
+            });
+        }
+    }
+
+    Mesh::new(points, faces)
+}
@@ -0,0 +1,157 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use rand::random;
+
+use crate::{
+    csg::{boundary_intervals, Interval, Solid},
+    hit::{surrounding_box, Aabb, HitRecord, Hitable, MatPtr, Material, Ray, SurfaceSample},
+    light::{area_pdf_value, area_random},
+    math::{poly::solve_quadratic, vec2, vec3, Onb, Vec3},
+};
+
+use super::disk::{disk_extent, Disk};
+
+// lateral surface of a cone with its base circle around `base` and its tip at `apex`
+pub struct Cone {
+    base: Vec3,
+    height: f64,
+    radius: f64,
+    uvw: Onb,
+    material: Arc<dyn Material>,
+    cap: Option<Disk>,
+}
+
+impl Cone {
+    pub fn new(base: Vec3, apex: Vec3, radius: f64, material: impl MatPtr) -> Self {
+        let axis = apex - base;
+        Self {
+            base,
+            height: axis.length(),
+            radius,
+            uvw: Onb::build_from(&axis),
+            material: material.into(),
+            cap: None,
+        }
+    }
+
+    // closes the base with a disk
+    pub fn capped(self) -> Self {
+        let cap = Disk::new(
+            self.base,
+            -*self.uvw.w(),
+            self.radius,
+            self.material.clone(),
+        );
+        Self {
+            cap: Some(cap),
+            ..self
+        }
+    }
+
+    fn side_area(&self) -> f64 {
+        PI * self.radius * self.radius.hypot(self.height)
+    }
+
+    fn hit_side(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let o = self.uvw.to_local(&(r.origin() - self.base));
+        let d = self.uvw.to_local(r.direction());
+
+        // x^2 + y^2 = k^2 (h - z)^2
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let h = self.height - o.z();
+        let a = d.x() * d.x() + d.y() * d.y() - k2 * d.z() * d.z();
+        let b = 2. * (o.x() * d.x() + o.y() * d.y() + k2 * h * d.z());
+        let c = o.x() * o.x() + o.y() * o.y() - k2 * h * h;
+
+        let mut roots = solve_quadratic([c, b, a]);
+        roots.sort_by(|a, b| a.total_cmp(b));
+
+        roots
+            .into_iter()
+            .filter(|t| t_min <= *t && *t <= t_max)
+            .map(|t| (t, o + t * d))
+            .find(|(_, p)| 0. <= p.z() && p.z() <= self.height)
+            .map(|(t, p)| {
+                let normal = vec3(p.x(), p.y(), k2 * (self.height - p.z()));
+                let normal = self.uvw.local(&normal).normalize();
+                let phi = f64::atan2(p.y(), p.x()) + PI;
+                let uv = vec2(phi / (2. * PI), p.z() / self.height);
+                HitRecord::new(r, t, r.at(t), normal, uv, self.material.as_ref())
+            })
+    }
+}
+
+impl Hitable for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let side = self.hit_side(r, t_min, t_max);
+        let t_max = side.as_ref().map_or(t_max, |rec| rec.t);
+        match &self.cap {
+            Some(cap) => cap.hit(r, t_min, t_max).or(side),
+            None => side,
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = disk_extent(self.uvw.w(), self.radius) + 0.0001;
+        let apex = self.base + self.height * self.uvw.w();
+        surrounding_box(
+            &Aabb::new(self.base - extent, self.base + extent),
+            &Aabb::new(apex - 0.0001, apex + 0.0001),
+        )
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        area_pdf_value(self, o, v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        area_random(self, o)
+    }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        let area = self.area();
+        if let Some(cap) = &self.cap {
+            if random::<f64>() * area < cap.area() {
+                let sample = cap.sample_surface()?;
+                return Some(SurfaceSample {
+                    pdf: 1. / area,
+                    ..sample
+                });
+            }
+        }
+
+        // the lateral area grows linearly away from the apex
+        let (u, v) = (random::<f64>(), random::<f64>().sqrt());
+        let (sin, cos) = (2. * PI * u).sin_cos();
+        let z = self.height * (1. - v);
+        let p = vec3(self.radius * v * cos, self.radius * v * sin, z);
+        let k = self.radius / self.height;
+        let normal = vec3(cos, sin, k).normalize();
+        Some(SurfaceSample {
+            p: self.base + self.uvw.local(&p),
+            normal: self.uvw.local(&normal),
+            uv: vec2((f64::atan2(sin, cos) + PI) / (2. * PI), 1. - v),
+            pdf: 1. / area,
+            material: self.material.as_ref(),
+        })
+    }
+
+    fn area(&self) -> f64 {
+        self.side_area() + self.cap.as_ref().map_or(0., |cap| cap.area())
+    }
+
+    fn power(&self) -> Vec3 {
+        self.material.power(self.area())
+    }
+}
+
+// only a capped cone encloses anything, without its base it has no inside
+impl Solid for Cone {
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        match self.cap {
+            Some(_) => boundary_intervals(self, r),
+            None => Vec::new(),
+        }
+    }
+}
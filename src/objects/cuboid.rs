@@ -1,12 +1,16 @@
-use crate::objects::rect::{XyRect, XzRect, YzRect};
+use crate::objects::{
+    quad::Quad,
+    rect::{XyRect, XzRect, YzRect},
+};
 use crate::{
-    hit::{Aabb, HitRecord, Hitable, MatPtr, Ray},
-    math::{vec2, Vec3},
+    csg::{boundary_intervals, Interval, Solid},
+    hit::{surrounding_box, Aabb, HitRecord, Hitable, MatPtr, Ray, SurfaceSample},
+    light::{area_pdf_value, area_random},
+    math::{cross, dot, vec2, Vec3},
     transform::HitableExt,
 };
 pub struct Cuboid {
-    pmin: Vec3,
-    pmax: Vec3,
+    bbox: Aabb,
     faces: [Box<dyn Hitable>; 6],
 }
 
@@ -63,11 +67,37 @@ impl Cuboid {
         ];
 
         Self {
-            pmin: p0,
-            pmax: p1,
+            bbox: Aabb::new(p0, p1),
             faces,
         }
     }
+
+    // parallelepiped spanned by the edges `x`, `y` and `z` from `corner`, the faces point outward
+    // whatever the handedness of the edges
+    pub fn oriented(corner: Vec3, x: Vec3, y: Vec3, z: Vec3, material: impl MatPtr) -> Self {
+        let material = material.into();
+        let (x, y) = if dot(&cross(&x, &y), &z) < 0. {
+            (y, x)
+        } else {
+            (x, y)
+        };
+
+        let faces = [
+            Quad::new(corner + z, x, y, material.clone()).boxed(),
+            Quad::new(corner, y, x, material.clone()).boxed(),
+            Quad::new(corner + x, y, z, material.clone()).boxed(),
+            Quad::new(corner, z, y, material.clone()).boxed(),
+            Quad::new(corner + y, z, x, material.clone()).boxed(),
+            Quad::new(corner, x, z, material).boxed(),
+        ];
+        let bbox = faces[1..]
+            .iter()
+            .fold(faces[0].bounding_box(), |bbox, face| {
+                surrounding_box(&bbox, &face.bounding_box())
+            });
+
+        Self { bbox, faces }
+    }
 }
 
 impl Hitable for Cuboid {
@@ -76,14 +106,32 @@ impl Hitable for Cuboid {
     }
 
     fn bounding_box(&self) -> Aabb {
-        Aabb::new(self.pmin, self.pmax)
+        self.bbox
     }
 
     fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
-        self.faces.pdf_value(o, v)
+        area_pdf_value(self, o, v)
     }
 
     fn random(&self, o: &Vec3) -> Vec3 {
-        self.faces.random(o)
+        area_random(self, o)
+    }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        self.faces.sample_surface()
+    }
+
+    fn area(&self) -> f64 {
+        self.faces.area()
+    }
+
+    fn power(&self) -> Vec3 {
+        self.faces.power()
+    }
+}
+
+impl Solid for Cuboid {
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        boundary_intervals(self, r)
     }
 }
@@ -0,0 +1,154 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use rand::random;
+
+use crate::{
+    csg::{boundary_intervals, Interval, Solid},
+    hit::{surrounding_box, Aabb, HitRecord, Hitable, MatPtr, Material, Ray, SurfaceSample},
+    light::{area_pdf_value, area_random},
+    math::{poly::solve_quadratic, vec2, vec3, Onb, Vec3},
+};
+
+use super::disk::{disk_extent, Disk};
+
+// open tube around the segment from `base` to `base + axis`, see `capped` for a solid
+pub struct Cylinder {
+    base: Vec3,
+    height: f64,
+    radius: f64,
+    uvw: Onb,
+    material: Arc<dyn Material>,
+    caps: Option<[Disk; 2]>,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3, axis: Vec3, radius: f64, material: impl MatPtr) -> Self {
+        Self {
+            base,
+            height: axis.length(),
+            radius,
+            uvw: Onb::build_from(&axis),
+            material: material.into(),
+            caps: None,
+        }
+    }
+
+    pub fn capped(self) -> Self {
+        let axis = self.height * self.uvw.w();
+        let caps = [
+            Disk::new(self.base, -axis, self.radius, self.material.clone()),
+            Disk::new(self.base + axis, axis, self.radius, self.material.clone()),
+        ];
+        Self {
+            caps: Some(caps),
+            ..self
+        }
+    }
+
+    fn side_area(&self) -> f64 {
+        2. * PI * self.radius * self.height
+    }
+
+    fn hit_side(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let o = self.uvw.to_local(&(r.origin() - self.base));
+        let d = self.uvw.to_local(r.direction());
+
+        let a = d.x() * d.x() + d.y() * d.y();
+        let b = 2. * (o.x() * d.x() + o.y() * d.y());
+        let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+
+        let mut roots = solve_quadratic([c, b, a]);
+        roots.sort_by(|a, b| a.total_cmp(b));
+
+        roots
+            .into_iter()
+            .filter(|t| t_min <= *t && *t <= t_max)
+            .map(|t| (t, o + t * d))
+            .find(|(_, p)| 0. <= p.z() && p.z() <= self.height)
+            .map(|(t, p)| {
+                let normal = self.uvw.local(&vec3(p.x(), p.y(), 0.)) / self.radius;
+                let phi = f64::atan2(p.y(), p.x()) + PI;
+                let uv = vec2(phi / (2. * PI), p.z() / self.height);
+                HitRecord::new(r, t, r.at(t), normal, uv, self.material.as_ref())
+            })
+    }
+}
+
+impl Hitable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest = self.hit_side(r, t_min, t_max);
+        if let Some(caps) = &self.caps {
+            for cap in caps {
+                let t_max = closest.as_ref().map_or(t_max, |rec| rec.t);
+                if let Some(rec) = cap.hit(r, t_min, t_max) {
+                    closest = Some(rec);
+                }
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = disk_extent(self.uvw.w(), self.radius) + 0.0001;
+        let top = self.base + self.height * self.uvw.w();
+        surrounding_box(
+            &Aabb::new(self.base - extent, self.base + extent),
+            &Aabb::new(top - extent, top + extent),
+        )
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        area_pdf_value(self, o, v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        area_random(self, o)
+    }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        let area = self.area();
+        let mut pick = random::<f64>() * area;
+        if let Some(caps) = &self.caps {
+            for cap in caps {
+                if pick < cap.area() {
+                    let sample = cap.sample_surface()?;
+                    return Some(SurfaceSample {
+                        pdf: 1. / area,
+                        ..sample
+                    });
+                }
+                pick -= cap.area();
+            }
+        }
+
+        let (u, v) = (random::<f64>(), random::<f64>());
+        let (sin, cos) = (2. * PI * u).sin_cos();
+        let normal = self.uvw.local(&vec3(cos, sin, 0.));
+        Some(SurfaceSample {
+            p: self.base + self.radius * normal + v * self.height * self.uvw.w(),
+            normal,
+            uv: vec2((f64::atan2(sin, cos) + PI) / (2. * PI), v),
+            pdf: 1. / area,
+            material: self.material.as_ref(),
+        })
+    }
+
+    fn area(&self) -> f64 {
+        let caps = self.caps.as_ref().map_or(0., |caps| 2. * caps[0].area());
+        self.side_area() + caps
+    }
+
+    fn power(&self) -> Vec3 {
+        self.material.power(self.area())
+    }
+}
+
+// only a capped cylinder encloses anything, an open tube has no inside
+impl Solid for Cylinder {
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        match self.caps {
+            Some(_) => boundary_intervals(self, r),
+            None => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use rand::random;
+
+use crate::{
+    hit::{Aabb, HitRecord, Hitable, MatPtr, Material, Ray, SurfaceSample},
+    light::{area_pdf_value, area_random},
+    math::{vec2, vec3, Onb, Vec3},
+};
+
+pub struct Disk {
+    center: Vec3,
+    radius: f64,
+    uvw: Onb,
+    material: Arc<dyn Material>,
+}
+
+impl Disk {
+    // the disk faces along `normal`
+    pub fn new(center: Vec3, normal: Vec3, radius: f64, material: impl MatPtr) -> Self {
+        Self {
+            center,
+            radius,
+            uvw: Onb::build_from(&normal),
+            material: material.into(),
+        }
+    }
+}
+
+impl Hitable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let o = self.uvw.to_local(&(r.origin() - self.center));
+        let d = self.uvw.to_local(r.direction());
+        if d.z() == 0. {
+            return None;
+        }
+
+        let t = -o.z() / d.z();
+        if t < t_min || t_max < t {
+            return None;
+        }
+        let (x, y) = (o.x() + t * d.x(), o.y() + t * d.y());
+        let distance_squared = x * x + y * y;
+        if distance_squared > self.radius * self.radius {
+            return None;
+        }
+
+        let phi = f64::atan2(y, x) + PI;
+        let uv = vec2(phi / (2. * PI), distance_squared.sqrt() / self.radius);
+        Some(HitRecord::new(
+            r,
+            t,
+            r.at(t),
+            *self.uvw.w(),
+            uv,
+            self.material.as_ref(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = disk_extent(self.uvw.w(), self.radius) + 0.0001;
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        area_pdf_value(self, o, v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        area_random(self, o)
+    }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        let (u, v) = (random::<f64>(), random::<f64>());
+        let (sin, cos) = (2. * PI * v).sin_cos();
+        let radius = self.radius * u.sqrt();
+        Some(SurfaceSample {
+            p: self.center + self.uvw.local(&vec3(radius * cos, radius * sin, 0.)),
+            normal: *self.uvw.w(),
+            uv: vec2((f64::atan2(sin, cos) + PI) / (2. * PI), u.sqrt()),
+            pdf: 1. / self.area(),
+            material: self.material.as_ref(),
+        })
+    }
+
+    fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+
+    fn power(&self) -> Vec3 {
+        self.material.power(self.area())
+    }
+}
+
+// half size of the box around a circle of `radius` facing along `normal`
+pub fn disk_extent(normal: &Vec3, radius: f64) -> Vec3 {
+    let n = normal.normalize();
+    n.map(|c| radius * (1. - c * c).max(0.).sqrt())
+}
@@ -1,3 +1,10 @@
+pub mod cone;
 pub mod cuboid;
+pub mod cylinder;
+pub mod disk;
+pub mod plane;
+pub mod quad;
 pub mod rect;
 pub mod sphere;
+pub mod torus;
+pub mod triangle;
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use crate::{
+    hit::{Aabb, HitRecord, Hitable, MatPtr, Material, Ray},
+    math::{dot, vec2, vec3, Onb, Vec3},
+};
+
+// infinite plane through `point`. Textures repeat every `uv_scale` units along the plane.
+pub struct Plane {
+    point: Vec3,
+    uvw: Onb,
+    uv_scale: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, material: impl MatPtr) -> Self {
+        Self {
+            point,
+            uvw: Onb::build_from(&normal),
+            uv_scale: 1.,
+            material: material.into(),
+        }
+    }
+
+    pub fn uv_scale(self, uv_scale: f64) -> Self {
+        Self { uv_scale, ..self }
+    }
+}
+
+impl Hitable for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let normal = self.uvw.w();
+        let denominator = dot(r.direction(), normal);
+        if denominator == 0. {
+            return None;
+        }
+
+        let t = dot(&(self.point - r.origin()), normal) / denominator;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let p = r.at(t);
+        let local = self.uvw.to_local(&(p - self.point)) / self.uv_scale;
+        let uv = vec2(local.x().rem_euclid(1.), local.y().rem_euclid(1.));
+        Some(HitRecord::new(r, t, p, *normal, uv, self.material.as_ref()))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(Vec3::new1(f64::NEG_INFINITY), Vec3::new1(f64::INFINITY))
+    }
+
+    // an infinite surface can't be sampled as a light
+    fn pdf_value(&self, _o: &Vec3, _v: &Vec3) -> f64 {
+        0.
+    }
+
+    fn random(&self, _o: &Vec3) -> Vec3 {
+        vec3(1, 0, 0)
+    }
+}
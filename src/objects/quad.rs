@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use rand::random;
+
+use crate::{
+    hit::{surrounding_box, Aabb, HitRecord, Hitable, MatPtr, Material, Ray, SurfaceSample},
+    light::{area_pdf_value, area_random},
+    math::{cross, dot, vec2, Vec2, Vec3},
+};
+
+// parallelogram spanned by the edges `u` and `v` from the corner `q`, facing along u × v
+pub struct Quad {
+    q: Vec3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    // n / (n · n), projects hit points onto the edges
+    w: Vec3,
+    area: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: impl MatPtr) -> Self {
+        let n = cross(&u, &v);
+        Self {
+            q,
+            u,
+            v,
+            normal: n.normalize(),
+            w: n / dot(&n, &n),
+            area: n.length(),
+            material: material.into(),
+        }
+    }
+}
+
+impl Hitable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denominator = dot(&self.normal, r.direction());
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = dot(&self.normal, &(self.q - r.origin())) / denominator;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let p = r.at(t);
+        let planar = p - self.q;
+        let alpha = dot(&self.w, &cross(&planar, &self.v));
+        let beta = dot(&self.w, &cross(&self.u, &planar));
+        if !(0. ..=1.).contains(&alpha) || !(0. ..=1.).contains(&beta) {
+            return None;
+        }
+
+        Some(
+            HitRecord::new(
+                r,
+                t,
+                p,
+                self.normal,
+                vec2(alpha, beta),
+                self.material.as_ref(),
+            )
+            .tangents(self.u, self.v),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let start = Aabb::new(self.q - 0.0001, self.q + 0.0001);
+        let corners = [self.u, self.v, self.u + self.v];
+        corners.iter().fold(start, |bbox, corner| {
+            let p = self.q + corner;
+            surrounding_box(&bbox, &Aabb::new(p - 0.0001, p + 0.0001))
+        })
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        area_pdf_value(self, o, v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        area_random(self, o)
+    }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        self.sample_uv(vec2(random::<f64>(), random::<f64>()))
+    }
+
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        Some(SurfaceSample {
+            p: self.q + uv.u() * self.u + uv.v() * self.v,
+            normal: self.normal,
+            uv,
+            pdf: 1. / self.area,
+            material: self.material.as_ref(),
+        })
+    }
+
+    fn area(&self) -> f64 {
+        self.area
+    }
+
+    fn power(&self) -> Vec3 {
+        self.material.power(self.area())
+    }
+}
@@ -3,7 +3,7 @@ use std::{marker::PhantomData, sync::Arc};
 use rand::{thread_rng, Rng};
 
 use crate::{
-    hit::{Aabb, HitRecord, Hitable, MatPtr, Material, Ray},
+    hit::{Aabb, HitRecord, Hitable, MatPtr, Material, Ray, SurfaceSample},
     math::{dot, vec2, vec3, Vec2, Vec3},
 };
 
@@ -112,14 +112,19 @@ where
             (b - self.b[0]) / (self.b[1] - self.b[0]),
         );
 
-        Some(HitRecord::new(
-            r,
-            t,
-            r.at(t),
-            T::permute(0.0, 0.0, 1.0),
-            uv,
-            self.mat.as_ref(),
-        ))
+        let dpdu = T::permute(self.a[1] - self.a[0], 0.0, 0.0);
+        let dpdv = T::permute(0.0, self.b[1] - self.b[0], 0.0);
+        Some(
+            HitRecord::new(
+                r,
+                t,
+                r.at(t),
+                T::permute(0.0, 0.0, 1.0),
+                uv,
+                self.mat.as_ref(),
+            )
+            .tangents(dpdu, dpdv),
+        )
     }
 
     fn bounding_box(&self) -> crate::hit::Aabb {
@@ -131,11 +136,10 @@ where
 
     fn pdf_value(&self, origin: &Vec3, v: &Vec3) -> f64 {
         if let Some(rec) = self.hit(&Ray::new(*origin, *v), 0.001, f64::INFINITY) {
-            let area = (self.a[1] - self.a[0]) * (self.b[1] - self.b[0]);
             let distance_squared = rec.t * rec.t * v.length_squared();
             let cosine = dot(&v, &rec.normal).abs() / v.length();
 
-            distance_squared / (cosine * area)
+            distance_squared / (cosine * self.area())
         } else {
             0.0
         }
@@ -150,6 +154,31 @@ where
         );
         random_point - o
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        let mut rng = thread_rng();
+        self.sample_uv(vec2(rng.gen(), rng.gen()))
+    }
+
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        let a = self.a[0] + uv.u() * (self.a[1] - self.a[0]);
+        let b = self.b[0] + uv.v() * (self.b[1] - self.b[0]);
+        Some(SurfaceSample {
+            p: T::permute(a, b, self.k),
+            normal: T::permute(0.0, 0.0, 1.0),
+            uv,
+            pdf: 1. / self.area(),
+            material: self.mat.as_ref(),
+        })
+    }
+
+    fn area(&self) -> f64 {
+        (self.a[1] - self.a[0]) * (self.b[1] - self.b[0])
+    }
+
+    fn power(&self) -> Vec3 {
+        self.mat.power(self.area())
+    }
 }
 
 pub type XyRect = Rect<XY>;
@@ -3,9 +3,10 @@ use std::{f64::consts::PI, sync::Arc};
 use rand::random;
 
 use crate::{
+    csg::{Interval, Solid},
     hit::MatPtr,
-    hit::{Aabb, HitRecord, Hitable, Material, Ray},
-    math::{dot, vec2, vec3, Onb, Vec2, Vec3},
+    hit::{Aabb, HitRecord, Hitable, Material, Ray, SurfaceSample},
+    math::{dot, random_unit_vector, vec2, vec3, Onb, Vec2, Vec3},
 };
 
 pub struct Sphere {
@@ -22,10 +23,9 @@ impl Sphere {
             material: material.into(),
         }
     }
-}
 
-impl Hitable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    // both crossings of the line through `r`, nearest first
+    fn roots(&self, r: &Ray) -> Option<(f64, f64)> {
         let oc = r.origin() - self.center;
         let a = dot(r.direction(), r.direction());
         let half_b = dot(&oc, r.direction());
@@ -36,25 +36,38 @@ impl Hitable for Sphere {
             return None;
         }
         let sqrtd = discriminant.sqrt();
-        let mut root = (-half_b - sqrtd) / a;
-
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
-            }
-        }
+        Some(((-half_b - sqrtd) / a, (-half_b + sqrtd) / a))
+    }
 
-        let p = r.at(root);
+    fn record(&self, r: &Ray, t: f64) -> HitRecord {
+        let p = r.at(t);
         let outward_normal = (p - self.center) / self.radius;
-        return Some(HitRecord::new(
+        let (dpdu, dpdv) = sphere_tangents(&outward_normal, self.radius);
+        HitRecord::new(
             r,
-            root,
+            t,
             p,
             outward_normal,
             get_sphere_uv(outward_normal),
             self.material.as_ref(),
-        ));
+        )
+        .tangents(dpdu, dpdv)
+    }
+}
+
+impl Hitable for Sphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (near, far) = self.roots(r)?;
+        let mut root = near;
+
+        if root < t_min || t_max < root {
+            root = far;
+            if root < t_min || t_max < root {
+                return None;
+            }
+        }
+
+        Some(self.record(r, root))
     }
 
     fn bounding_box(&self) -> Aabb {
@@ -79,6 +92,52 @@ impl Hitable for Sphere {
 
         uvw.local(&random_to_sphere(&self.radius, &distance_squared))
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        let normal = random_unit_vector();
+        Some(SurfaceSample {
+            p: self.center + self.radius * normal,
+            normal,
+            uv: get_sphere_uv(normal),
+            pdf: 1. / self.area(),
+            material: self.material.as_ref(),
+        })
+    }
+
+    // uniform texture coordinates crowd towards the poles, where the map squeezes rows together
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        let (sin_theta, cos_theta) = (PI * uv.v()).sin_cos();
+        let (sin_phi, cos_phi) = (2. * PI * uv.u()).sin_cos();
+        let normal = vec3(-sin_theta * cos_phi, -cos_theta, sin_theta * sin_phi);
+        let jacobian = 2. * PI * PI * self.radius * self.radius * sin_theta;
+        Some(SurfaceSample {
+            p: self.center + self.radius * normal,
+            normal,
+            uv,
+            pdf: 1. / jacobian.max(1e-12),
+            material: self.material.as_ref(),
+        })
+    }
+
+    fn area(&self) -> f64 {
+        4. * PI * self.radius * self.radius
+    }
+
+    fn power(&self) -> Vec3 {
+        self.material.power(self.area())
+    }
+}
+
+impl Solid for Sphere {
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        match self.roots(r) {
+            Some((near, far)) if near < far => vec![Interval {
+                enter: self.record(r, near),
+                exit: self.record(r, far),
+            }],
+            _ => Vec::new(),
+        }
+    }
 }
 
 fn random_to_sphere(radius: &f64, distance_squared: &f64) -> Vec3 {
@@ -98,3 +157,17 @@ fn get_sphere_uv(p: Vec3) -> Vec2 {
     let phi = f64::atan2(-p.z(), p.x()) + PI;
     vec2(phi / (2. * PI), theta / PI)
 }
+
+// derivatives of the mapping in `get_sphere_uv`, degenerate at the poles
+fn sphere_tangents(n: &Vec3, radius: f64) -> (Vec3, Vec3) {
+    let dpdu = 2. * PI * radius * vec3(n.z(), 0., -n.x());
+    let sin_theta = (1. - n.y() * n.y()).sqrt().max(1e-9);
+    let dpdv = PI
+        * radius
+        * vec3(
+            -n.x() * n.y() / sin_theta,
+            sin_theta,
+            -n.y() * n.z() / sin_theta,
+        );
+    (dpdu, dpdv)
+}
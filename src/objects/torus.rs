@@ -0,0 +1,129 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use rand::random;
+
+use crate::{
+    csg::{boundary_intervals, Interval, Solid},
+    hit::{Aabb, HitRecord, Hitable, MatPtr, Material, Ray, SurfaceSample},
+    light::{area_pdf_value, area_random},
+    math::{poly::solve_quartic, vec2, vec3, Onb, Vec2, Vec3},
+};
+
+// ring around `axis` through `center`, the tube of radius `minor` follows a circle of radius
+// `major`
+pub struct Torus {
+    center: Vec3,
+    major: f64,
+    minor: f64,
+    uvw: Onb,
+    material: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(center: Vec3, axis: Vec3, major: f64, minor: f64, material: impl MatPtr) -> Self {
+        Self {
+            center,
+            major,
+            minor,
+            uvw: Onb::build_from(&axis),
+            material: material.into(),
+        }
+    }
+
+    fn uv(&self, p: &Vec3) -> Vec2 {
+        let rho = p.x().hypot(p.y());
+        let phi = f64::atan2(p.y(), p.x()) + PI;
+        let theta = f64::atan2(p.z(), rho - self.major);
+        vec2(phi / (2. * PI), theta / (2. * PI) + 0.5)
+    }
+}
+
+impl Hitable for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let o = self.uvw.to_local(&(r.origin() - self.center));
+        let d = self.uvw.to_local(r.direction());
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2)
+        let (r2, rr2) = (self.major * self.major, self.minor * self.minor);
+        let dd = d.length_squared();
+        let od = o.x() * d.x() + o.y() * d.y() + o.z() * d.z();
+        let k = o.length_squared() + r2 - rr2;
+        let coefficients = [
+            k * k - 4. * r2 * (o.x() * o.x() + o.y() * o.y()),
+            4. * od * k - 8. * r2 * (o.x() * d.x() + o.y() * d.y()),
+            2. * dd * k + 4. * od * od - 4. * r2 * (d.x() * d.x() + d.y() * d.y()),
+            4. * dd * od,
+            dd * dd,
+        ];
+
+        let t = solve_quartic(coefficients)
+            .into_iter()
+            .filter(|t| t_min <= *t && *t <= t_max)
+            .min_by(|a, b| a.partial_cmp(b).unwrap())?;
+
+        let p = o + t * d;
+        let ring = vec3(p.x(), p.y(), 0.).normalize() * self.major;
+        let normal = self.uvw.local(&((p - ring) / self.minor));
+        Some(HitRecord::new(
+            r,
+            t,
+            r.at(t),
+            normal,
+            self.uv(&p),
+            self.material.as_ref(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let w = self.uvw.w().normalize();
+        let outer = self.major + self.minor;
+        let extent = w.map(|c| outer * (1. - c * c).max(0.).sqrt() + self.minor * c.abs());
+        let extent = extent + 0.0001;
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        area_pdf_value(self, o, v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        area_random(self, o)
+    }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        // the outside of the tube has more area than the inside, reject angles accordingly
+        let theta = loop {
+            let theta = 2. * PI * random::<f64>();
+            let accept = (self.major + self.minor * theta.cos()) / (self.major + self.minor);
+            if random::<f64>() < accept {
+                break theta;
+            }
+        };
+        let (sin_phi, cos_phi) = (2. * PI * random::<f64>()).sin_cos();
+        let (sin_theta, cos_theta) = theta.sin_cos();
+
+        let normal = vec3(cos_theta * cos_phi, cos_theta * sin_phi, sin_theta);
+        let p = vec3(self.major * cos_phi, self.major * sin_phi, 0.) + self.minor * normal;
+        Some(SurfaceSample {
+            p: self.center + self.uvw.local(&p),
+            normal: self.uvw.local(&normal),
+            uv: self.uv(&p),
+            pdf: 1. / self.area(),
+            material: self.material.as_ref(),
+        })
+    }
+
+    fn area(&self) -> f64 {
+        4. * PI * PI * self.major * self.minor
+    }
+
+    fn power(&self) -> Vec3 {
+        self.material.power(self.area())
+    }
+}
+
+impl Solid for Torus {
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        boundary_intervals(self, r)
+    }
+}
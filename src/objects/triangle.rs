@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use rand::random;
+
+use crate::{
+    hit::{Aabb, HitRecord, Hitable, MatPtr, Material, Ray, SurfaceSample},
+    light::{area_pdf_value, area_random},
+    math::{cross, dot, vec2, Onb, Vec2, Vec3},
+};
+
+pub struct Triangle {
+    p: [Vec3; 3],
+    // shading normals at the corners, the geometric normal when flat
+    normals: [Vec3; 3],
+    uvs: [Vec2; 3],
+    material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    // faces along (p1 - p0) × (p2 - p0)
+    pub fn new(p: [Vec3; 3], material: impl MatPtr) -> Self {
+        let normal = cross(&(p[1] - p[0]), &(p[2] - p[0])).normalize();
+        Self {
+            p,
+            normals: [normal; 3],
+            uvs: [vec2(0., 0.), vec2(1., 0.), vec2(0., 1.)],
+            material: material.into(),
+        }
+    }
+
+    // interpolates the normals across the face
+    pub fn smooth(self, normals: [Vec3; 3]) -> Self {
+        Self { normals, ..self }
+    }
+
+    pub fn uvs(self, uvs: [Vec2; 3]) -> Self {
+        Self { uvs, ..self }
+    }
+
+    // solves for the derivatives along the edges, any frame around the normal will do where the
+    // texture coordinates are degenerate
+    fn tangents(&self) -> (Vec3, Vec3) {
+        let (duv02, duv12) = (self.uvs[0] - self.uvs[2], self.uvs[1] - self.uvs[2]);
+        let (dp02, dp12) = (self.p[0] - self.p[2], self.p[1] - self.p[2]);
+        let det = duv02.u() * duv12.v() - duv02.v() * duv12.u();
+        if det.abs() < 1e-12 {
+            let uvw = Onb::build_from(&cross(&(self.p[1] - self.p[0]), &(self.p[2] - self.p[0])));
+            return (*uvw.u(), *uvw.v());
+        }
+
+        let dpdu = (duv12.v() * dp02 - duv02.v() * dp12) / det;
+        let dpdv = (duv02.u() * dp12 - duv12.u() * dp02) / det;
+        (dpdu, dpdv)
+    }
+
+    fn interpolate(&self, b1: f64, b2: f64) -> (Vec3, Vec3, Vec2) {
+        let b0 = 1. - b1 - b2;
+        let p = b0 * self.p[0] + b1 * self.p[1] + b2 * self.p[2];
+        let normal = b0 * self.normals[0] + b1 * self.normals[1] + b2 * self.normals[2];
+        let uv = b0 * self.uvs[0] + b1 * self.uvs[1] + b2 * self.uvs[2];
+        (p, normal.normalize(), uv)
+    }
+}
+
+impl Hitable for Triangle {
+    // Möller-Trumbore
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let e1 = self.p[1] - self.p[0];
+        let e2 = self.p[2] - self.p[0];
+        let pvec = cross(r.direction(), &e2);
+        let det = dot(&e1, &pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1. / det;
+
+        let tvec = r.origin() - self.p[0];
+        let b1 = dot(&tvec, &pvec) * inv_det;
+        if !(0. ..=1.).contains(&b1) {
+            return None;
+        }
+        let qvec = cross(&tvec, &e1);
+        let b2 = dot(r.direction(), &qvec) * inv_det;
+        if b2 < 0. || b1 + b2 > 1. {
+            return None;
+        }
+
+        let t = dot(&e2, &qvec) * inv_det;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let (_, normal, uv) = self.interpolate(b1, b2);
+        let (dpdu, dpdv) = self.tangents();
+        Some(HitRecord::new(r, t, r.at(t), normal, uv, self.material.as_ref()).tangents(dpdu, dpdv))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let mut min = self.p[0];
+        let mut max = self.p[0];
+        for p in &self.p[1..] {
+            for a in 0..3 {
+                min[a] = min[a].min(p[a]);
+                max[a] = max[a].max(p[a]);
+            }
+        }
+        Aabb::new(min - 0.0001, max + 0.0001)
+    }
+
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        area_pdf_value(self, o, v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        area_random(self, o)
+    }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        let (u, v) = (random::<f64>().sqrt(), random::<f64>());
+        let (p, normal, uv) = self.interpolate(u * (1. - v), u * v);
+        Some(SurfaceSample {
+            p,
+            normal,
+            uv,
+            pdf: 1. / self.area(),
+            material: self.material.as_ref(),
+        })
+    }
+
+    fn area(&self) -> f64 {
+        cross(&(self.p[1] - self.p[0]), &(self.p[2] - self.p[0])).length() / 2.
+    }
+
+    fn power(&self) -> Vec3 {
+        self.material.power(self.area())
+    }
+}
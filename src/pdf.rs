@@ -3,6 +3,7 @@ use std::f64::consts::PI;
 use rand::random;
 
 use crate::{
+    environment::Environment,
     hit::{Hitable, Pdf},
     math::{dot, random_cosine_direction, Onb, Vec3},
 };
@@ -58,14 +59,40 @@ where
     }
 }
 
+pub struct EnvironmentPdf<'a> {
+    env: &'a dyn Environment,
+}
+
+impl<'a> EnvironmentPdf<'a> {
+    pub fn new(env: &'a dyn Environment) -> Self {
+        Self { env }
+    }
+}
+
+impl Pdf for EnvironmentPdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.env.pdf_value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.env.random()
+    }
+}
+
 pub struct MixturePdf<P0, P1> {
     p0: P0,
     p1: P1,
+    // probability of sampling `p1`
+    weight: f64,
 }
 
 impl<P0, P1> MixturePdf<P0, P1> {
     pub fn new(p1: P0, p2: P1) -> Self {
-        Self { p0: p1, p1: p2 }
+        Self::weighted(p1, p2, 0.5)
+    }
+
+    pub fn weighted(p0: P0, p1: P1, weight: f64) -> Self {
+        Self { p0, p1, weight }
     }
 }
 
@@ -75,14 +102,14 @@ where
     P1: Pdf,
 {
     fn value(&self, direction: &Vec3) -> f64 {
-        0.5 * self.p0.value(direction) + 0.5 * self.p1.value(direction)
+        (1. - self.weight) * self.p0.value(direction) + self.weight * self.p1.value(direction)
     }
 
     fn generate(&self) -> Vec3 {
-        if random::<f64>() < 0.5 {
-            self.p0.generate()
-        } else {
+        if random::<f64>() < self.weight {
             self.p1.generate()
+        } else {
+            self.p0.generate()
         }
     }
 }
@@ -0,0 +1,394 @@
+use std::{collections::HashMap, f64::consts::PI};
+
+use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
+use rand::{random, thread_rng, Rng};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+
+use crate::{
+    hit::{Aabb, HitRecord, Hitable, Ray, ScatterKind},
+    light::{PhotonSample, PunctualLight},
+    math::{dot, random_cosine_direction, vec2, Onb, Vec3},
+    sample_punctual, to_rgba,
+    volume::MediumStack,
+    Scene,
+};
+
+// fraction of new photons kept per pass, controls how fast the radius shrinks
+const ALPHA: f64 = 2. / 3.;
+const MAX_DEPTH: usize = 50;
+
+struct Photon {
+    p: Vec3,
+    wi: Vec3,
+    power: Vec3,
+}
+
+struct PhotonGrid {
+    cell_size: f64,
+    cells: HashMap<(i64, i64, i64), Vec<Photon>>,
+}
+
+impl PhotonGrid {
+    fn new(photons: Vec<Photon>, cell_size: f64) -> Self {
+        let mut cells: HashMap<_, Vec<Photon>> = HashMap::new();
+        for photon in photons {
+            cells
+                .entry(cell(&photon.p, cell_size))
+                .or_default()
+                .push(photon);
+        }
+
+        Self { cell_size, cells }
+    }
+
+    fn for_each_near(&self, p: &Vec3, radius: f64, mut f: impl FnMut(&Photon)) {
+        let (x, y, z) = cell(p, self.cell_size);
+        let radius_squared = radius * radius;
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let photons = match self.cells.get(&(x + dx, y + dy, z + dz)) {
+                        Some(photons) => photons,
+                        None => continue,
+                    };
+                    photons
+                        .iter()
+                        .filter(|photon| (photon.p - p).length_squared() <= radius_squared)
+                        .for_each(&mut f);
+                }
+            }
+        }
+    }
+}
+
+fn cell(p: &Vec3, cell_size: f64) -> (i64, i64, i64) {
+    let c = p.map(|c| (c / cell_size).floor());
+    (c.x() as i64, c.y() as i64, c.z() as i64)
+}
+
+struct VisiblePoint<'m> {
+    r_in: Ray,
+    rec: HitRecord<'m>,
+    beta: Vec3,
+}
+
+struct PixelStats {
+    radius: f64,
+    n: f64,
+    tau: Vec3,
+    direct: Vec3,
+}
+
+fn trace_camera<'m>(mut r: Ray, scene: &'m Scene) -> (Vec3, Option<VisiblePoint<'m>>) {
+    let mut direct = Vec3::zero();
+    let mut beta = Vec3::new1(1.);
+
+    for _ in 0..MAX_DEPTH {
+        let mut rec = match scene.world.hit(&r, 0.001, f64::INFINITY) {
+            None => return (direct + beta * scene.background.value(r.direction()), None),
+            Some(rec) => rec,
+        };
+        rec.set_footprint(&r);
+        direct += beta * rec.material.emitted(&r, &rec, rec.uv, &rec.p);
+
+        let scatter = match rec.material.scatter(&r, &rec) {
+            None => break,
+            Some(scatter) => scatter,
+        };
+
+        match scatter.kind() {
+            ScatterKind::Diffuse { pdf } => {
+                direct += beta
+                    * sample_punctual(scene, &rec.p, None, |shadow_ray| {
+                        let f = scatter.attenuation()
+                            * rec.material.scattering_pdf(&r, &rec, shadow_ray);
+                        (f, MediumStack::new())
+                    });
+
+                // photons are only stored on surfaces, media are walked through
+                if rec.material.is_volumetric() {
+                    let scattered = Ray::new(rec.p, pdf.generate());
+                    let pdf = pdf.value(scattered.direction());
+                    if pdf <= 0. {
+                        break;
+                    }
+                    beta = beta
+                        * scatter.attenuation()
+                        * rec.material.scattering_pdf(&r, &rec, &scattered)
+                        / pdf;
+                    r = scattered;
+                    continue;
+                }
+
+                let beta = beta * scatter.attenuation();
+                return (direct, Some(VisiblePoint { r_in: r, rec, beta }));
+            }
+            ScatterKind::Specular { specular_ray } => {
+                beta = beta * scatter.attenuation();
+                r = *specular_ray;
+            }
+        }
+    }
+
+    (direct, None)
+}
+
+// a photon leaving one of the area lights
+fn emit_area(lights: &dyn Hitable) -> Option<PhotonSample> {
+    let sample = match lights.sample_surface() {
+        Some(sample) if sample.pdf > 0. => sample,
+        _ => return None,
+    };
+
+    // two-sided emitters leave from either side, each picked half the time
+    let (normal, sides) = if !sample.material.is_two_sided() {
+        (sample.normal, 1.)
+    } else if random::<f64>() < 0.5 {
+        (sample.normal, 2.)
+    } else {
+        (-sample.normal, 2.)
+    };
+    let direction = Onb::build_from(&normal).local(&random_cosine_direction());
+    let emitted = sample.emitted(&direction);
+    if emitted.near_zero() {
+        return None;
+    }
+
+    // cosine-weighted emission: cos / (pdf_area * cos / pi)
+    Some(PhotonSample {
+        ray: Ray::new(sample.p, direction),
+        power: sides * emitted * PI / sample.pdf,
+    })
+}
+
+// area and punctual lights, picked for each photon by their power
+struct Emitters<'s> {
+    lights: Option<&'s dyn Hitable>,
+    punctual: &'s [Box<dyn PunctualLight>],
+    bounds: Aabb,
+    cdf: Vec<f64>,
+}
+
+impl<'s> Emitters<'s> {
+    fn new(scene: &'s Scene, bounds: Aabb) -> Self {
+        let lights = scene.lights.as_deref();
+        let punctual = scene.punctual_lights.as_slice();
+        let weights = lights
+            .map(|lights| lights.power().mean())
+            .into_iter()
+            .chain(punctual.iter().map(|light| light.power(&bounds).mean()))
+            .map(|w| if w.is_finite() { w.max(0.) } else { 0. });
+
+        let mut total = 0.;
+        let mut cdf: Vec<_> = weights
+            .map(|w| {
+                total += w;
+                total
+            })
+            .collect();
+        cdf.iter_mut().for_each(|c| *c /= total);
+
+        Self {
+            lights,
+            punctual,
+            bounds,
+            cdf,
+        }
+    }
+
+    // also whether the photon came from a punctual light
+    fn emit(&self) -> Option<(PhotonSample, bool)> {
+        let u = random::<f64>();
+        let i = self.cdf.iter().position(|c| u < *c)?;
+        let probability = self.cdf[i] - if i > 0 { self.cdf[i - 1] } else { 0. };
+
+        let photon = match (self.lights, i) {
+            (Some(lights), 0) => emit_area(lights),
+            (Some(_), i) => self.punctual[i - 1].emit(&self.bounds),
+            (None, i) => self.punctual[i].emit(&self.bounds),
+        }?;
+        let photon = PhotonSample {
+            power: photon.power / probability,
+            ..photon
+        };
+        Some((photon, self.lights.is_none() || i > 0))
+    }
+}
+
+fn trace_photon(emitters: &Emitters, world: &dyn Hitable, photons: &mut Vec<Photon>) {
+    let (
+        PhotonSample {
+            ray: mut r,
+            mut power,
+        },
+        punctual,
+    ) = match emitters.emit() {
+        Some(photon) => photon,
+        None => return,
+    };
+    // the camera pass already samples punctual lights directly, only light that bounced off
+    // something on the way is left to the photons
+    let mut direct = punctual;
+    let emitted_power = power.x().max(power.y()).max(power.z());
+
+    for depth in 0..MAX_DEPTH {
+        let rec = match world.hit(&r, 0.001, f64::INFINITY) {
+            None => return,
+            Some(rec) => rec,
+        };
+        let scatter = match rec.material.scatter(&r, &rec) {
+            None => return,
+            Some(scatter) => scatter,
+        };
+
+        match scatter.kind() {
+            ScatterKind::Diffuse { pdf } => {
+                if !rec.material.is_volumetric() && !direct {
+                    photons.push(Photon {
+                        p: rec.p,
+                        wi: -r.direction().normalize(),
+                        power,
+                    });
+                }
+
+                let scattered = Ray::new(rec.p, pdf.generate());
+                let pdf = pdf.value(scattered.direction());
+                if pdf <= 0. {
+                    return;
+                }
+                power = power
+                    * scatter.attenuation()
+                    * rec.material.scattering_pdf(&r, &rec, &scattered)
+                    / pdf;
+                r = scattered;
+            }
+            ScatterKind::Specular { specular_ray } => {
+                power = power * scatter.attenuation();
+                r = *specular_ray;
+            }
+        }
+
+        direct = false;
+
+        // survival by the throughput since emission, the power itself is far above 1
+        if depth > 3 {
+            let q = (power.x().max(power.y()).max(power.z()) / emitted_power).min(1.);
+            if random::<f64>() > q {
+                return;
+            }
+            power = power / q;
+        }
+    }
+}
+
+impl Scene {
+    /// Renders with stochastic progressive photon mapping. Every pass traces one camera path
+    /// per pixel to its first diffuse vertex and gathers photons emitted from `lights` and
+    /// `punctual_lights` around it. The background is only seen directly, it emits no photons.
+    pub fn fill_buf_sppm(
+        &self,
+        nx: usize,
+        ny: usize,
+        passes: usize,
+        photons_per_pass: usize,
+    ) -> Vec<[u8; 4]> {
+        // unbounded shapes such as planes are left out, they'd make the radius infinite
+        let bbox = self
+            .world
+            .finite_bounding_box()
+            .unwrap_or_else(|| Aabb::new(Vec3::zero(), Vec3::new1(1.)));
+        let initial_radius = (bbox.max() - bbox.min()).length() * 0.005;
+        let emitters = Emitters::new(self, bbox);
+
+        let mut stats: Vec<PixelStats> = (0..nx * ny)
+            .map(|_| PixelStats {
+                radius: initial_radius,
+                n: 0.,
+                tau: Vec3::zero(),
+                direct: Vec3::zero(),
+            })
+            .collect();
+
+        let progress = ProgressBar::new(passes as u64);
+        progress.set_style(ProgressStyle::default_bar().template(
+            "[{elapsed_precise}] [{eta_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
+        ));
+
+        for _pass in (0..passes).progress_with(progress.clone()) {
+            let visible: Vec<_> = (0..nx * ny)
+                .into_par_iter()
+                .map(|n| {
+                    let i = n % nx;
+                    let j = ny - n / nx;
+
+                    let mut rng = thread_rng();
+                    let u = (i as f64 + rng.gen::<f64>()) / nx as f64;
+                    let v = (j as f64 + rng.gen::<f64>()) / ny as f64;
+
+                    let pixel = vec2(1. / nx as f64, 1. / ny as f64);
+                    trace_camera(self.cam.get_ray(u, v, pixel), self)
+                })
+                .collect();
+
+            let photons = (0..photons_per_pass)
+                .into_par_iter()
+                .fold(Vec::new, |mut photons, _| {
+                    trace_photon(&emitters, self.world.as_ref(), &mut photons);
+                    photons
+                })
+                .flatten()
+                .collect();
+
+            let max_radius = stats.iter().map(|s| s.radius).fold(0., f64::max);
+            let grid = PhotonGrid::new(photons, max_radius);
+
+            stats
+                .par_iter_mut()
+                .zip(visible.into_par_iter())
+                .for_each(|(stats, (direct, vp))| {
+                    stats.direct += direct;
+
+                    let vp = match vp {
+                        Some(vp) => vp,
+                        None => return,
+                    };
+
+                    let mut m = 0.;
+                    let mut phi = Vec3::zero();
+                    grid.for_each_near(&vp.rec.p, stats.radius, |photon| {
+                        let cosine = dot(&photon.wi, &vp.rec.normal).abs();
+                        if cosine <= 0. {
+                            return;
+                        }
+                        let wi = Ray::new(vp.rec.p, photon.wi);
+                        let f = vp.rec.material.scattering_pdf(&vp.r_in, &vp.rec, &wi) / cosine;
+                        m += 1.;
+                        phi += vp.beta * f * photon.power;
+                    });
+
+                    if m > 0. {
+                        let n = stats.n + ALPHA * m;
+                        let radius = stats.radius * f64::sqrt(n / (stats.n + m));
+                        let shrink = (radius * radius) / (stats.radius * stats.radius);
+
+                        stats.tau = (stats.tau + phi) * shrink;
+                        stats.n = n;
+                        stats.radius = radius;
+                    }
+                });
+        }
+        progress.finish();
+
+        let emitted = (passes * photons_per_pass) as f64;
+        stats
+            .into_iter()
+            .map(|stats| {
+                let indirect = stats.tau / (emitted * PI * stats.radius * stats.radius);
+                to_rgba(stats.direct / passes as f64 + indirect)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,169 @@
+// Implicit surfaces given by signed distance functions, found by sphere tracing: a point at
+// distance d from the surface can always step d along the ray without crossing it.
+
+use std::sync::Arc;
+
+use crate::{
+    csg::{boundary_intervals, Interval, Solid},
+    hit::{Aabb, HitRecord, Hitable, MatPtr, Material, Ray},
+    math::{dot, vec2, vec3, Vec3},
+};
+
+mod shapes;
+pub use shapes::*;
+
+pub trait DistanceField: Send + Sync {
+    // signed distance to the surface, negative inside. Overestimating it makes rays skip
+    // through the surface.
+    fn distance(&self, p: &Vec3) -> f64;
+
+    // conservative box around the surface
+    fn bounds(&self) -> Aabb {
+        Aabb::new(Vec3::new1(f64::NEG_INFINITY), Vec3::new1(f64::INFINITY))
+    }
+}
+
+impl<F> DistanceField for F
+where
+    F: Fn(&Vec3) -> f64 + Send + Sync,
+{
+    fn distance(&self, p: &Vec3) -> f64 {
+        self(p)
+    }
+}
+
+// direction of steepest increase, estimated from four samples on a tetrahedron around `p`
+pub fn gradient(field: &(impl DistanceField + ?Sized), p: &Vec3, h: f64) -> Vec3 {
+    [
+        vec3(1, -1, -1),
+        vec3(-1, -1, 1),
+        vec3(-1, 1, -1),
+        vec3(1, 1, 1),
+    ]
+    .iter()
+    .map(|k| *k * field.distance(&(p + h * k)))
+    .sum()
+}
+
+pub struct Sdf<F> {
+    field: F,
+    bbox: Aabb,
+    epsilon: f64,
+    max_steps: usize,
+    step_scale: f64,
+    material: Arc<dyn Material>,
+}
+
+impl<F> Sdf<F>
+where
+    F: DistanceField,
+{
+    pub fn new(field: F, material: impl MatPtr) -> Self {
+        Self {
+            bbox: field.bounds(),
+            field,
+            epsilon: 1e-4,
+            max_steps: 256,
+            step_scale: 1.,
+            material: material.into(),
+        }
+    }
+
+    // replaces the bounds reported by the field, needed for closures
+    pub fn bounds(self, bbox: Aabb) -> Self {
+        Self { bbox, ..self }
+    }
+
+    // distance at which a ray counts as touching the surface
+    pub fn epsilon(self, epsilon: f64) -> Self {
+        Self { epsilon, ..self }
+    }
+
+    pub fn max_steps(self, max_steps: usize) -> Self {
+        Self { max_steps, ..self }
+    }
+
+    // fraction of the distance taken per step, fields that overestimate (twists, fractals) need
+    // less than 1
+    pub fn step_scale(self, step_scale: f64) -> Self {
+        Self { step_scale, ..self }
+    }
+
+    fn normal(&self, p: &Vec3) -> Vec3 {
+        gradient(&self.field, p, self.epsilon).normalize()
+    }
+}
+
+impl<F> Hitable for Sdf<F>
+where
+    F: DistanceField,
+{
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (mut t, t_end) = self.bbox.interval(r, t_min, t_max)?;
+        if !t.is_finite() {
+            return None;
+        }
+        let length = r.direction().length();
+
+        // distances are measured on the side the ray starts on. A ray leaving the surface first
+        // has to get clear of it, otherwise it would hit its own starting point. Rays entering
+        // the box start outside, even where the surface touches the box.
+        let entering = t > t_min;
+        let mut side = if entering { 1. } else { 0. };
+        let mut clear = entering;
+        for _ in 0..self.max_steps {
+            let p = r.at(t);
+            let distance = self.field.distance(&p);
+            if side == 0. {
+                side = if distance.abs() >= self.epsilon {
+                    distance.signum()
+                } else {
+                    dot(&self.normal(&p), r.direction()).signum()
+                };
+            }
+
+            let distance = side * distance;
+            if clear && distance < self.epsilon {
+                let normal = self.normal(&p);
+                return Some(HitRecord::new(
+                    r,
+                    t,
+                    p,
+                    normal,
+                    vec2(0., 0.),
+                    self.material.as_ref(),
+                ));
+            }
+            clear |= distance >= self.epsilon;
+
+            t += self.step_scale * distance.max(self.epsilon) / length;
+            if t > t_end {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // the surface area isn't known, so it can't be sampled as a light
+    fn pdf_value(&self, _o: &Vec3, _v: &Vec3) -> f64 {
+        0.0
+    }
+
+    fn random(&self, _o: &Vec3) -> Vec3 {
+        vec3(1, 0, 0)
+    }
+}
+
+impl<F> Solid for Sdf<F>
+where
+    F: DistanceField,
+{
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        boundary_intervals(self, r)
+    }
+}
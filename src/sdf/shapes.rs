@@ -0,0 +1,365 @@
+use crate::{
+    hit::{surrounding_box, Aabb},
+    math::{dot, vec2, vec3, Vec3},
+};
+
+use super::DistanceField;
+
+fn pad(bbox: Aabb, amount: f64) -> Aabb {
+    Aabb::new(bbox.min() - amount, bbox.max() + amount)
+}
+
+// shapes are centered on the origin, place them with `translate`
+
+pub struct Sphere {
+    radius: f64,
+}
+
+impl Sphere {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl DistanceField for Sphere {
+    fn distance(&self, p: &Vec3) -> f64 {
+        p.length() - self.radius
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::new(Vec3::new1(-self.radius), Vec3::new1(self.radius))
+    }
+}
+
+pub struct Cuboid {
+    half_size: Vec3,
+}
+
+impl Cuboid {
+    pub fn new(size: Vec3) -> Self {
+        Self {
+            half_size: size / 2.,
+        }
+    }
+}
+
+impl DistanceField for Cuboid {
+    fn distance(&self, p: &Vec3) -> f64 {
+        let q = p.map(f64::abs) - self.half_size;
+        let outside = q.map(|c| c.max(0.)).length();
+        let inside = q.x().max(q.y()).max(q.z()).min(0.);
+        outside + inside
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::new(-self.half_size, self.half_size)
+    }
+}
+
+// ring in the xz plane
+pub struct Torus {
+    major: f64,
+    minor: f64,
+}
+
+impl Torus {
+    pub fn new(major: f64, minor: f64) -> Self {
+        Self { major, minor }
+    }
+}
+
+impl DistanceField for Torus {
+    fn distance(&self, p: &Vec3) -> f64 {
+        let ring = p.x().hypot(p.z()) - self.major;
+        ring.hypot(p.y()) - self.minor
+    }
+
+    fn bounds(&self) -> Aabb {
+        let outer = self.major + self.minor;
+        let extent = vec3(outer, self.minor, outer);
+        Aabb::new(-extent, extent)
+    }
+}
+
+// segment from `a` to `b` thickened by `radius`
+pub struct Capsule {
+    a: Vec3,
+    b: Vec3,
+    radius: f64,
+}
+
+impl Capsule {
+    pub fn new(a: Vec3, b: Vec3, radius: f64) -> Self {
+        Self { a, b, radius }
+    }
+}
+
+impl DistanceField for Capsule {
+    fn distance(&self, p: &Vec3) -> f64 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = (dot(&pa, &ba) / dot(&ba, &ba)).clamp(0., 1.);
+        (pa - h * ba).length() - self.radius
+    }
+
+    fn bounds(&self) -> Aabb {
+        pad(
+            surrounding_box(&Aabb::new(self.a, self.a), &Aabb::new(self.b, self.b)),
+            self.radius,
+        )
+    }
+}
+
+// distance estimate of the power 8 mandelbulb, which fits into a box of size 2.5. Overestimates
+// near the surface, render it with a step scale below 1.
+pub struct Mandelbulb {
+    power: f64,
+    iterations: usize,
+}
+
+impl Mandelbulb {
+    pub fn new(power: f64, iterations: usize) -> Self {
+        Self { power, iterations }
+    }
+}
+
+impl Default for Mandelbulb {
+    fn default() -> Self {
+        Self::new(8., 12)
+    }
+}
+
+impl DistanceField for Mandelbulb {
+    fn distance(&self, p: &Vec3) -> f64 {
+        let mut z = *p;
+        let mut dr = 1.;
+        let mut r = 0.;
+        for _ in 0..self.iterations {
+            r = z.length();
+            if r > 2. {
+                break;
+            }
+
+            let theta = (z.z() / r).acos() * self.power;
+            let phi = z.y().atan2(z.x()) * self.power;
+            dr = r.powf(self.power - 1.) * self.power * dr + 1.;
+
+            let (sin_theta, cos_theta) = theta.sin_cos();
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            z = r.powf(self.power) * vec3(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta) + p;
+        }
+        0.5 * r.ln() * r / dr
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::new(Vec3::new1(-1.25), Vec3::new1(1.25))
+    }
+}
+
+pub struct Translate<F> {
+    inner: F,
+    offset: Vec3,
+}
+
+impl<F> DistanceField for Translate<F>
+where
+    F: DistanceField,
+{
+    fn distance(&self, p: &Vec3) -> f64 {
+        self.inner.distance(&(p - self.offset))
+    }
+
+    fn bounds(&self) -> Aabb {
+        let bbox = self.inner.bounds();
+        Aabb::new(bbox.min() + self.offset, bbox.max() + self.offset)
+    }
+}
+
+pub struct Union<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> DistanceField for Union<A, B>
+where
+    A: DistanceField,
+    B: DistanceField,
+{
+    fn distance(&self, p: &Vec3) -> f64 {
+        self.a.distance(p).min(self.b.distance(p))
+    }
+
+    fn bounds(&self) -> Aabb {
+        surrounding_box(&self.a.bounds(), &self.b.bounds())
+    }
+}
+
+// union blending the shapes together where they are closer than `k`, a plain union for k <= 0
+pub struct SmoothUnion<A, B> {
+    a: A,
+    b: B,
+    k: f64,
+}
+
+impl<A, B> DistanceField for SmoothUnion<A, B>
+where
+    A: DistanceField,
+    B: DistanceField,
+{
+    fn distance(&self, p: &Vec3) -> f64 {
+        let (a, b) = (self.a.distance(p), self.b.distance(p));
+        if self.k <= 0. {
+            return a.min(b);
+        }
+        let h = (self.k - (a - b).abs()).max(0.) / self.k;
+        a.min(b) - h * h * self.k / 4.
+    }
+
+    // the blend pulls the surface out by at most k/4
+    fn bounds(&self) -> Aabb {
+        pad(
+            surrounding_box(&self.a.bounds(), &self.b.bounds()),
+            self.k.max(0.) / 4.,
+        )
+    }
+}
+
+// rounds off edges, growing the shape by `radius`
+pub struct Round<F> {
+    inner: F,
+    radius: f64,
+}
+
+impl<F> DistanceField for Round<F>
+where
+    F: DistanceField,
+{
+    fn distance(&self, p: &Vec3) -> f64 {
+        self.inner.distance(p) - self.radius
+    }
+
+    fn bounds(&self) -> Aabb {
+        pad(self.inner.bounds(), self.radius)
+    }
+}
+
+// rotates around the y axis by `rate` radians per unit of height. Not a true distance any more,
+// render it with a step scale below 1.
+pub struct Twist<F> {
+    inner: F,
+    rate: f64,
+}
+
+impl<F> DistanceField for Twist<F>
+where
+    F: DistanceField,
+{
+    fn distance(&self, p: &Vec3) -> f64 {
+        let (sin, cos) = (self.rate * p.y()).sin_cos();
+        let q = vec3(cos * p.x() - sin * p.z(), p.y(), sin * p.x() + cos * p.z());
+        self.inner.distance(&q)
+    }
+
+    fn bounds(&self) -> Aabb {
+        let bbox = self.inner.bounds();
+        let (min, max) = (bbox.min(), bbox.max());
+        let radius = [
+            vec2(min.x(), min.z()),
+            vec2(min.x(), max.z()),
+            vec2(max.x(), min.z()),
+            vec2(max.x(), max.z()),
+        ]
+        .iter()
+        .map(|c| c.u().hypot(c.v()))
+        .fold(0., f64::max);
+        Aabb::new(
+            vec3(-radius, min.y(), -radius),
+            vec3(radius, max.y(), radius),
+        )
+    }
+}
+
+// infinite copies every `period` along each axis, a zero component leaves that axis alone. The
+// shape should fit into one cell.
+pub struct Repeat<F> {
+    inner: F,
+    period: Vec3,
+}
+
+impl<F> DistanceField for Repeat<F>
+where
+    F: DistanceField,
+{
+    fn distance(&self, p: &Vec3) -> f64 {
+        let mut q = *p;
+        for a in 0..3 {
+            if self.period[a] > 0. {
+                q[a] -= self.period[a] * (p[a] / self.period[a]).round();
+            }
+        }
+        self.inner.distance(&q)
+    }
+
+    fn bounds(&self) -> Aabb {
+        let bbox = self.inner.bounds();
+        let (mut min, mut max) = (bbox.min(), bbox.max());
+        for a in 0..3 {
+            if self.period[a] > 0. {
+                min[a] = f64::NEG_INFINITY;
+                max[a] = f64::INFINITY;
+            }
+        }
+        Aabb::new(min, max)
+    }
+}
+
+pub trait DistanceFieldExt: Sized {
+    fn translate(self, offset: Vec3) -> Translate<Self>;
+    fn union<B: DistanceField>(self, other: B) -> Union<Self, B>;
+    fn smooth_union<B: DistanceField>(self, other: B, k: f64) -> SmoothUnion<Self, B>;
+    fn round(self, radius: f64) -> Round<Self>;
+    fn twist(self, rate: f64) -> Twist<Self>;
+    fn repeat(self, period: Vec3) -> Repeat<Self>;
+}
+
+impl<T> DistanceFieldExt for T
+where
+    T: DistanceField,
+{
+    fn translate(self, offset: Vec3) -> Translate<Self> {
+        Translate {
+            inner: self,
+            offset,
+        }
+    }
+
+    fn union<B: DistanceField>(self, other: B) -> Union<Self, B> {
+        Union { a: self, b: other }
+    }
+
+    fn smooth_union<B: DistanceField>(self, other: B, k: f64) -> SmoothUnion<Self, B> {
+        SmoothUnion {
+            a: self,
+            b: other,
+            k,
+        }
+    }
+
+    fn round(self, radius: f64) -> Round<Self> {
+        Round {
+            inner: self,
+            radius,
+        }
+    }
+
+    fn twist(self, rate: f64) -> Twist<Self> {
+        Twist { inner: self, rate }
+    }
+
+    fn repeat(self, period: Vec3) -> Repeat<Self> {
+        Repeat {
+            inner: self,
+            period,
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::f64::consts::PI;
+
+use crate::{
+    environment::Environment,
+    light::DirectionalLight,
+    math::{dot, vec3, Vec3},
+    spectrum::{blackbody_rgb, xyz_to_rgb},
+};
+
+const SUN_ANGULAR_DIAMETER: f64 = 0.53;
+
+// Preetham, Shirley, Smits: "A Practical Analytic Model for Daylight".
+// Luminances come out in kcd/m^2 and are scaled by `intensity` into scene units.
+pub struct PreethamSky {
+    sun: Vec3,
+    turbidity: f64,
+    intensity: f64,
+    zenith: Vec3,
+    perez: [[f64; 5]; 3],
+    ground: Vec3,
+}
+
+impl PreethamSky {
+    // `sun` points towards the sun, y is up
+    pub fn new(sun: Vec3, turbidity: f64, ground_albedo: Vec3) -> Self {
+        let sun = sun.normalize();
+        let t = turbidity;
+        let theta_s = f64::acos(sun.y().max(0.));
+
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let thetas = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.];
+        let poly = |t2: [f64; 4], t1: [f64; 4], t0: [f64; 4]| -> f64 {
+            (0..4)
+                .map(|i| (t * t * t2[i] + t * t1[i] + t0[i]) * thetas[i])
+                .sum()
+        };
+        let zenith_x = poly(
+            [0.00166, -0.00375, 0.00209, 0.],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        );
+        let zenith_y = poly(
+            [0.00275, -0.00610, 0.00317, 0.],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        );
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let mut sky = Self {
+            sun,
+            turbidity,
+            intensity: 0.05,
+            zenith: vec3(zenith_luminance, zenith_x, zenith_y),
+            perez,
+            ground: Vec3::zero(),
+        };
+        sky.ground = ground_albedo * sky.ground_irradiance() / PI;
+        sky
+    }
+
+    // solar position for a site and a UTC time, see `sun_direction`
+    pub fn at(
+        latitude: f64,
+        longitude: f64,
+        day_of_year: u32,
+        hour_utc: f64,
+        turbidity: f64,
+        ground_albedo: Vec3,
+    ) -> Self {
+        let sun = sun_direction(latitude, longitude, day_of_year, hour_utc);
+        Self::new(sun, turbidity, ground_albedo)
+    }
+
+    pub fn intensity(self, intensity: f64) -> Self {
+        let ground = self.ground * intensity / self.intensity;
+        Self {
+            intensity,
+            ground,
+            ..self
+        }
+    }
+
+    // the sun disc as a light, to be added to the scene's punctual lights
+    pub fn sun(&self) -> DirectionalLight {
+        DirectionalLight::new(self.sun, self.sun_irradiance(), SUN_ANGULAR_DIAMETER)
+    }
+
+    fn sun_irradiance(&self) -> Vec3 {
+        if self.sun.y() <= 0. {
+            return Vec3::zero();
+        }
+
+        // clear sky illuminance of roughly 100 klux, attenuated along the optical path
+        let zenith_degrees = f64::acos(self.sun.y()).to_degrees();
+        let air_mass = 1. / (self.sun.y() + 0.15 * f64::powf(93.885 - zenith_degrees, -1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+
+        let lambdas = vec3(0.680, 0.550, 0.440);
+        let rayleigh = lambdas.map(|l| 0.008735 * l.powf(-4.08));
+        let aerosol = lambdas.map(|l| beta * l.powf(-1.3));
+        let transmittance = (-air_mass * (rayleigh + aerosol)).map(f64::exp);
+
+        self.intensity * 100. * transmittance * blackbody_rgb(5778.)
+    }
+
+    fn sky(&self, direction: &Vec3) -> Vec3 {
+        let cos_theta = direction.y().max(0.001);
+        let cos_gamma = dot(direction, &self.sun).clamp(-1., 1.);
+        let gamma = cos_gamma.acos();
+        let theta_s = f64::acos(self.sun.y().max(0.));
+
+        let perez = |c: &[f64; 5], cos_theta: f64, gamma: f64| {
+            (1. + c[0] * f64::exp(c[1] / cos_theta))
+                * (1. + c[2] * f64::exp(c[3] * gamma) + c[4] * gamma.cos().powi(2))
+        };
+        let channel = |i: usize| {
+            self.zenith[i] * perez(&self.perez[i], cos_theta, gamma)
+                / perez(&self.perez[i], 1., theta_s)
+        };
+
+        let (big_y, x, y) = (channel(0), channel(1), channel(2));
+        let xyz = vec3(x * big_y / y, big_y, (1. - x - y) * big_y / y);
+        self.intensity * xyz_to_rgb(&xyz).map(|c| c.max(0.))
+    }
+
+    fn ground_irradiance(&self) -> Vec3 {
+        let (nt, np) = (16, 32);
+        let d_theta = 0.5 * PI / nt as f64;
+        let d_phi = 2. * PI / np as f64;
+
+        let sky: Vec3 = (0..nt)
+            .flat_map(|i| (0..np).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let theta = (i as f64 + 0.5) * d_theta;
+                let phi = (j as f64 + 0.5) * d_phi;
+                let (sin_theta, cos_theta) = theta.sin_cos();
+                let direction = vec3(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+                self.sky(&direction) * cos_theta * sin_theta * d_theta * d_phi
+            })
+            .sum();
+
+        sky + self.sun_irradiance() * self.sun.y().max(0.)
+    }
+}
+
+impl Environment for PreethamSky {
+    fn value(&self, direction: &Vec3) -> Vec3 {
+        let direction = direction.normalize();
+        if direction.y() < 0. {
+            self.ground
+        } else {
+            self.sky(&direction)
+        }
+    }
+}
+
+// NOAA solar position approximation. Latitude and longitude are in degrees (north and east
+// positive), the result uses +x east, +y up and -z north.
+pub fn sun_direction(latitude: f64, longitude: f64, day_of_year: u32, hour_utc: f64) -> Vec3 {
+    let g = 2. * PI / 365. * (day_of_year as f64 - 1. + (hour_utc - 12.) / 24.);
+
+    let eqtime = 229.18
+        * (0.000075 + 0.001868 * g.cos()
+            - 0.032077 * g.sin()
+            - 0.014615 * (2. * g).cos()
+            - 0.040849 * (2. * g).sin());
+    let decl = 0.006918 - 0.399912 * g.cos() + 0.070257 * g.sin() - 0.006758 * (2. * g).cos()
+        + 0.000907 * (2. * g).sin()
+        - 0.002697 * (3. * g).cos()
+        + 0.00148 * (3. * g).sin();
+
+    let true_solar_time = hour_utc * 60. + eqtime + 4. * longitude;
+    let hour_angle = (true_solar_time / 4. - 180.).to_radians();
+    let lat = latitude.to_radians();
+
+    let east = -decl.cos() * hour_angle.sin();
+    let north = lat.cos() * decl.sin() - lat.sin() * decl.cos() * hour_angle.cos();
+    let up = lat.sin() * decl.sin() + lat.cos() * decl.cos() * hour_angle.cos();
+
+    vec3(east, up, -north).normalize()
+}
@@ -0,0 +1,265 @@
+use crate::math::{vec3, Vec3};
+
+pub const LAMBDA_MIN: f64 = 360.;
+pub const LAMBDA_MAX: f64 = 830.;
+
+pub fn luminance(rgb: &Vec3) -> f64 {
+    0.2126 * rgb.r() + 0.7152 * rgb.g() + 0.0722 * rgb.b()
+}
+
+// Planck's law, wavelength in nm
+pub fn blackbody(lambda: f64, kelvin: f64) -> f64 {
+    const C: f64 = 299_792_458.;
+    const H: f64 = 6.626_070_15e-34;
+    const KB: f64 = 1.380_649e-23;
+
+    let l = lambda * 1e-9;
+    2. * H * C * C / (l.powi(5) * (f64::exp(H * C / (l * KB * kelvin)) - 1.))
+}
+
+// multi-lobe gaussian fit of the CIE 1931 standard observer (Wyman et al. 2013)
+pub fn cie_xyz(lambda: f64) -> Vec3 {
+    fn g(x: f64, mu: f64, sigma1: f64, sigma2: f64) -> f64 {
+        let t = (x - mu) / if x < mu { sigma1 } else { sigma2 };
+        f64::exp(-0.5 * t * t)
+    }
+
+    vec3(
+        1.056 * g(lambda, 599.8, 37.9, 31.0) + 0.362 * g(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * g(lambda, 501.1, 20.4, 26.2),
+        0.821 * g(lambda, 568.8, 46.9, 40.5) + 0.286 * g(lambda, 530.9, 16.3, 31.1),
+        1.217 * g(lambda, 437.0, 11.8, 36.0) + 0.681 * g(lambda, 459.0, 26.0, 13.8),
+    )
+}
+
+// linear sRGB, D65 white
+pub fn xyz_to_rgb(xyz: &Vec3) -> Vec3 {
+    vec3(
+        3.2406 * xyz.x() - 1.5372 * xyz.y() - 0.4986 * xyz.z(),
+        -0.9689 * xyz.x() + 1.8758 * xyz.y() + 0.0415 * xyz.z(),
+        0.0557 * xyz.x() - 0.2040 * xyz.y() + 1.0570 * xyz.z(),
+    )
+}
+
+pub fn spectrum_to_xyz(spectrum: impl Fn(f64) -> f64) -> Vec3 {
+    let step = 5.;
+    let steps = ((LAMBDA_MAX - LAMBDA_MIN) / step) as usize;
+    (0..=steps)
+        .map(|i| LAMBDA_MIN + i as f64 * step)
+        .map(|lambda| spectrum(lambda) * cie_xyz(lambda))
+        .sum::<Vec3>()
+        * step
+}
+
+// colour of a blackbody at the given temperature, normalized to unit luminance
+pub fn blackbody_rgb(kelvin: f64) -> Vec3 {
+    let xyz = spectrum_to_xyz(|lambda| blackbody(lambda, kelvin));
+    let rgb = xyz_to_rgb(&(xyz / xyz.y())).map(|c| c.max(0.));
+    rgb / luminance(&rgb)
+}
+
+// integral of the CIE y curve over the visible range, the luminance of a flat unit spectrum
+pub const CIE_Y_INTEGRAL: f64 = 106.856895;
+
+// lumens per watt of light at 555 nm, where the eye is most sensitive
+pub const MAX_EFFICACY: f64 = 683.;
+
+// luminance in cd/m² that a scene radiance of unit luminance stands for. Lights given in
+// physical units are brought into scene units by it, so it plays the part of the exposure.
+// Lengths are taken to be in metres.
+pub const NITS_PER_UNIT: f64 = 1000.;
+
+// lumens per watt of a flat spectrum over the visible range
+pub fn flat_efficacy() -> f64 {
+    MAX_EFFICACY * CIE_Y_INTEGRAL / (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+// emission spectra for lights, see `DiffuseLight::spectrum`
+#[derive(Debug, Clone)]
+pub enum Spectrum {
+    Blackbody(f64),
+    // (wavelength in nm, value) pairs sorted by wavelength, linear in between
+    Tabulated(Vec<(f64, f64)>),
+}
+
+impl Spectrum {
+    pub fn tabulated(mut samples: Vec<(f64, f64)>) -> Self {
+        samples.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        Spectrum::Tabulated(samples)
+    }
+
+    pub fn value(&self, lambda: f64) -> f64 {
+        match self {
+            Spectrum::Blackbody(kelvin) => blackbody(lambda, *kelvin),
+            Spectrum::Tabulated(samples) => {
+                let i = samples.partition_point(|s| s.0 < lambda);
+                match (samples.get(i.wrapping_sub(1)), samples.get(i)) {
+                    (Some(a), Some(b)) => {
+                        let t = (lambda - a.0) / (b.0 - a.0);
+                        (1. - t) * a.1 + t * b.1
+                    }
+                    (Some(a), None) => a.1,
+                    (None, Some(b)) => b.1,
+                    (None, None) => 0.,
+                }
+            }
+        }
+    }
+
+    // values at a path's wavelengths, terminated wavelengths give zero
+    pub fn values(&self, lambdas: &Vec3) -> Vec3 {
+        let mut values = Vec3::zero();
+        for i in 0..3 {
+            if !lambdas[i].is_nan() {
+                values[i] = self.value(lambdas[i]);
+            }
+        }
+        values
+    }
+
+    pub fn to_xyz(&self) -> Vec3 {
+        spectrum_to_xyz(|lambda| self.value(lambda))
+    }
+
+    // factor that scales the spectrum to the luminance of a flat unit spectrum
+    pub fn normalization(&self) -> f64 {
+        let y = self.to_xyz().y();
+        if y > 0. {
+            CIE_Y_INTEGRAL / y
+        } else {
+            0.
+        }
+    }
+
+    // lumens per watt of radiant flux with this spectrum, over the visible range
+    pub fn efficacy(&self) -> f64 {
+        let step = 5.;
+        let steps = ((LAMBDA_MAX - LAMBDA_MIN) / step) as usize;
+        let radiant: f64 = (0..=steps)
+            .map(|i| self.value(LAMBDA_MIN + i as f64 * step))
+            .sum::<f64>()
+            * step;
+        if radiant > 0. {
+            MAX_EFFICACY * self.to_xyz().y() / radiant
+        } else {
+            0.
+        }
+    }
+
+    // rgb colour at unit luminance
+    pub fn to_rgb(&self) -> Vec3 {
+        let rgb = xyz_to_rgb(&self.to_xyz()).map(|c| c.max(0.));
+        let y = luminance(&rgb);
+        if y > 0. {
+            rgb / y
+        } else {
+            Vec3::zero()
+        }
+    }
+}
+
+// Hero wavelength sampling: three wavelengths per path, evenly rotated through the visible
+// range and importance sampled towards where the eye is sensitive (pbrt's visible pdf).
+pub fn sample_wavelengths(u: f64) -> Vec3 {
+    let sample = |u: f64| {
+        let u = u.fract();
+        // inverse cdf of the visible pdf restricted to [360, 830]
+        538. - 138.888889 * f64::atanh(0.85691062 - 1.82750197 * u)
+    };
+    vec3(sample(u), sample(u + 1. / 3.), sample(u + 2. / 3.))
+        .map(|l| l.clamp(LAMBDA_MIN, LAMBDA_MAX))
+}
+
+pub fn wavelength_pdf(lambda: f64) -> f64 {
+    if !(LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda) {
+        return 0.;
+    }
+    0.0039398042 / f64::cosh(0.0072 * (lambda - 538.)).powi(2)
+}
+
+// keeps only the hero wavelength, after a wavelength dependent event such as dispersion
+pub fn terminate_secondary(lambdas: &Vec3) -> Vec3 {
+    vec3(lambdas.x(), f64::NAN, f64::NAN)
+}
+
+// channel weights for a path whose wavelengths went from `before` to `after`
+pub fn termination_weight(before: &Vec3, after: &Vec3) -> Vec3 {
+    let alive = |l: &Vec3| (0..3).filter(|&i| !l[i].is_nan()).count() as f64;
+    let (n_before, n_after) = (alive(before), alive(after));
+    let mut weight = Vec3::zero();
+    for i in 0..3 {
+        if !after[i].is_nan() {
+            weight[i] = n_before / n_after;
+        }
+    }
+    weight
+}
+
+// Smits, "An RGB to Spectrum Conversion for Reflectances", 10 bins from 380 to 720 nm
+const SMITS_WHITE: [f64; 10] = [1., 1., 0.9999, 0.9993, 0.9992, 0.9998, 1., 1., 1., 1.];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0., 0., 0.,
+];
+const SMITS_MAGENTA: [f64; 10] = [1., 1., 0.9685, 0.2229, 0., 0.0458, 0.8369, 1., 1., 0.9959];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0., 0.1088, 0.6651, 1., 1., 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0., 0., 0., 0., 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [0., 0., 0.0273, 0.7937, 1., 0.9418, 0.1719, 0., 0., 0.0025];
+const SMITS_BLUE: [f64; 10] = [
+    1., 1., 0.8916, 0.3323, 0., 0., 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+// value of the smooth spectrum matching `rgb` at one wavelength
+pub fn rgb_to_spectrum(rgb: &Vec3, lambda: f64) -> f64 {
+    let bin = (((lambda - 380.) / 34.).floor().max(0.) as usize).min(9);
+    let (r, g, b) = (rgb.r(), rgb.g(), rgb.b());
+
+    let (base, first, second, a, c) = if r <= g && r <= b {
+        if g <= b {
+            (r, g - r, b - g, SMITS_CYAN, SMITS_BLUE)
+        } else {
+            (r, b - r, g - b, SMITS_CYAN, SMITS_GREEN)
+        }
+    } else if g <= r && g <= b {
+        if r <= b {
+            (g, r - g, b - r, SMITS_MAGENTA, SMITS_BLUE)
+        } else {
+            (g, b - g, r - b, SMITS_MAGENTA, SMITS_RED)
+        }
+    } else if r <= g {
+        (b, r - b, g - r, SMITS_YELLOW, SMITS_GREEN)
+    } else {
+        (b, g - b, r - g, SMITS_YELLOW, SMITS_RED)
+    };
+
+    base * SMITS_WHITE[bin] + first * a[bin] + second * c[bin]
+}
+
+// `rgb` upsampled at each of the path's wavelengths, terminated wavelengths give zero
+pub fn upsample(rgb: &Vec3, lambdas: &Vec3) -> Vec3 {
+    let mut values = Vec3::zero();
+    for i in 0..3 {
+        if !lambdas[i].is_nan() {
+            values[i] = rgb_to_spectrum(rgb, lambdas[i]);
+        }
+    }
+    values
+}
+
+// converts the radiance carried at `lambdas` into an rgb estimate
+pub fn spectral_to_rgb(values: &Vec3, lambdas: &Vec3) -> Vec3 {
+    let mut xyz = Vec3::zero();
+    for i in 0..3 {
+        let pdf = wavelength_pdf(lambdas[i]);
+        if !lambdas[i].is_nan() && pdf > 0. {
+            xyz += values[i] * cie_xyz(lambdas[i]) / pdf;
+        }
+    }
+    let xyz = xyz / (3. * CIE_Y_INTEGRAL);
+
+    // balanced so that a flat spectrum comes out white, like it does in rgb mode
+    xyz_to_rgb(&xyz) / xyz_to_rgb(&vec3(1, 1, 1))
+}
@@ -0,0 +1,131 @@
+use crate::math::{vec3, Vec2, Vec3};
+
+use super::Texture;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Metric {
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+}
+
+impl Metric {
+    fn distance(&self, d: &Vec3) -> f64 {
+        match self {
+            Metric::Euclidean => d.length(),
+            Metric::Manhattan => d.x().abs() + d.y().abs() + d.z().abs(),
+            Metric::Chebyshev => d.x().abs().max(d.y().abs()).max(d.z().abs()),
+        }
+    }
+}
+
+// which of the distances to the nearest feature points ends up in the texture
+#[derive(Debug, Clone, Copy)]
+pub enum Feature {
+    // to the nearest point, round cells
+    F1,
+    // to the second nearest
+    F2,
+    // difference of the two, dark along the borders between cells
+    F2MinusF1,
+}
+
+// Worley's cellular noise, one feature point scattered in every cube of side 1 / scale
+pub struct Worley {
+    scale: f64,
+    jitter: f64,
+    metric: Metric,
+    feature: Feature,
+    seed: u64,
+}
+
+impl Worley {
+    pub fn new(scale: f64) -> Self {
+        Self {
+            scale,
+            jitter: 1.,
+            metric: Metric::Euclidean,
+            feature: Feature::F1,
+            seed: 0,
+        }
+    }
+
+    pub fn seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    // how far the points stray from the cell centres, 0 gives a regular grid
+    pub fn jitter(self, jitter: f64) -> Self {
+        Self {
+            jitter: jitter.clamp(0., 1.),
+            ..self
+        }
+    }
+
+    pub fn metric(self, metric: Metric) -> Self {
+        Self { metric, ..self }
+    }
+
+    pub fn feature(self, feature: Feature) -> Self {
+        Self { feature, ..self }
+    }
+
+    // uniform in [0, 1) for each cell and `channel`
+    fn hash(&self, x: i64, y: i64, z: i64, channel: u64) -> f64 {
+        let mut h = (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ (y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+            ^ (z as u64).wrapping_mul(0x1656_67b1_9e37_79f9)
+            ^ channel.wrapping_mul(0x27d4_eb2f_1656_67c5)
+            ^ self.seed.wrapping_mul(0x85eb_ca77_c2b2_ae63);
+        // splitmix64 finalizer
+        h ^= h >> 30;
+        h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h ^= h >> 27;
+        h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
+        (h >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // the two smallest distances, in cells
+    fn nearest(&self, p: &Vec3) -> (f64, f64) {
+        let p = self.scale * p;
+        let cell = p.map(f64::floor);
+        let (mut f1, mut f2) = (f64::INFINITY, f64::INFINITY);
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let neighbour = cell + vec3(dx, dy, dz);
+                    let [x, y, z] = [neighbour.x(), neighbour.y(), neighbour.z()].map(|c| c as i64);
+                    let offset = vec3(
+                        self.hash(x, y, z, 0),
+                        self.hash(x, y, z, 1),
+                        self.hash(x, y, z, 2),
+                    );
+                    let point = neighbour + 0.5 + self.jitter * (offset - 0.5);
+
+                    let d = self.metric.distance(&(point - p));
+                    if d < f1 {
+                        f2 = f1;
+                        f1 = d;
+                    } else if d < f2 {
+                        f2 = d;
+                    }
+                }
+            }
+        }
+
+        (f1, f2)
+    }
+}
+
+impl Texture for Worley {
+    fn value(&self, _uv: Vec2, p: &Vec3) -> Vec3 {
+        let (f1, f2) = self.nearest(p);
+        Vec3::new1(match self.feature {
+            Feature::F1 => f1,
+            Feature::F2 => f2,
+            Feature::F2MinusF1 => f2 - f1,
+        })
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use crate::{
+    hit::Footprint,
+    math::{Vec2, Vec3},
+};
+
+use super::{query::Query, TexPtr, Texture};
+
+#[derive(Debug, Clone, Copy)]
+enum Space {
+    // cubes of side 1 / scale in world space
+    Solid(f64),
+    // a grid of u by v cells over the texture coordinates
+    Uv(f64, f64),
+}
+
+// alternates between two textures, `even` in the cell around the origin
+pub struct Checker {
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    space: Space,
+}
+
+impl Checker {
+    pub fn new(even: impl TexPtr, odd: impl TexPtr, scale: f64) -> Self {
+        Self {
+            even: even.into(),
+            odd: odd.into(),
+            space: Space::Solid(scale),
+        }
+    }
+
+    pub fn uv(even: impl TexPtr, odd: impl TexPtr, cells_u: f64, cells_v: f64) -> Self {
+        Self {
+            even: even.into(),
+            odd: odd.into(),
+            space: Space::Uv(cells_u, cells_v),
+        }
+    }
+
+    // cell coordinates of the point, and the size of the footprint in cells along each of them
+    fn cells(&self, uv: Vec2, p: &Vec3, footprint: &Footprint) -> Vec<(f64, f64)> {
+        let extent = |dx: f64, dy: f64| 2. * dx.abs().max(dy.abs());
+        match self.space {
+            Space::Solid(scale) => (0..3)
+                .map(|a| {
+                    let width = extent(footprint.dpdx[a], footprint.dpdy[a]);
+                    (scale * p[a], scale * width)
+                })
+                .collect(),
+            Space::Uv(cells_u, cells_v) => vec![
+                (
+                    cells_u * uv.u(),
+                    cells_u * extent(footprint.duvdx.u(), footprint.duvdy.u()),
+                ),
+                (
+                    cells_v * uv.v(),
+                    cells_v * extent(footprint.duvdx.v(), footprint.duvdy.v()),
+                ),
+            ],
+        }
+    }
+
+    // fraction of the footprint that lies in odd cells. The box filtered parity of each axis
+    // comes from integrating a square wave, the axes combine like independent coin flips.
+    fn odd_fraction(&self, uv: Vec2, p: &Vec3, footprint: &Footprint) -> f64 {
+        let even = self
+            .cells(uv, p, footprint)
+            .into_iter()
+            .map(|(x, width)| {
+                let odd = if width > 1e-8 {
+                    (odd_integral(x + width / 2.) - odd_integral(x - width / 2.)) / width
+                } else {
+                    x.floor().rem_euclid(2.)
+                };
+                1. - 2. * odd
+            })
+            .product::<f64>();
+        0.5 * (1. - even)
+    }
+}
+
+// length of the odd cells between 0 and x
+fn odd_integral(x: f64) -> f64 {
+    let pairs = (x / 2.).floor();
+    pairs + (x - 2. * pairs - 1.).max(0.)
+}
+
+impl Checker {
+    fn eval(&self, q: &Query) -> Vec3 {
+        let odd = self.odd_fraction(q.uv(), &q.p(), &q.footprint());
+        if odd <= 0. {
+            q.eval(self.even.as_ref())
+        } else if odd >= 1. {
+            q.eval(self.odd.as_ref())
+        } else {
+            (1. - odd) * q.eval(self.even.as_ref()) + odd * q.eval(self.odd.as_ref())
+        }
+    }
+}
+
+texture_from_query!(Checker);
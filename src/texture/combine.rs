@@ -0,0 +1,344 @@
+// Textures built from other textures. Each one passes the lookup it got on to its inputs, so
+// filtering and surface information reach the leaves.
+
+use std::sync::Arc;
+
+use crate::math::{cross, dot, vec2, vec3, Vec2, Vec3};
+
+use super::{query::Query, TexPtr, Texture};
+
+// blends from `a` at a factor of 0 to `b` at 1, by the mean of the factor texture
+pub struct Mix {
+    a: Arc<dyn Texture>,
+    b: Arc<dyn Texture>,
+    factor: Arc<dyn Texture>,
+}
+
+impl Mix {
+    pub fn new(a: impl TexPtr, b: impl TexPtr, factor: impl TexPtr) -> Self {
+        Self {
+            a: a.into(),
+            b: b.into(),
+            factor: factor.into(),
+        }
+    }
+
+    fn eval(&self, q: &Query) -> Vec3 {
+        let t = q.eval(self.factor.as_ref()).mean();
+        (1. - t) * q.eval(self.a.as_ref()) + t * q.eval(self.b.as_ref())
+    }
+}
+
+texture_from_query!(Mix);
+
+#[derive(Debug, Clone, Copy)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+}
+
+// per channel arithmetic on two textures
+pub struct Arithmetic {
+    op: Operator,
+    a: Arc<dyn Texture>,
+    b: Arc<dyn Texture>,
+}
+
+impl Arithmetic {
+    pub fn new(op: Operator, a: impl TexPtr, b: impl TexPtr) -> Self {
+        Self {
+            op,
+            a: a.into(),
+            b: b.into(),
+        }
+    }
+
+    fn eval(&self, q: &Query) -> Vec3 {
+        let (a, b) = (q.eval(self.a.as_ref()), q.eval(self.b.as_ref()));
+        match self.op {
+            Operator::Add => a + b,
+            Operator::Subtract => a - b,
+            Operator::Multiply => a * b,
+        }
+    }
+}
+
+texture_from_query!(Arithmetic);
+
+// one minus each channel
+pub struct Invert {
+    inner: Arc<dyn Texture>,
+}
+
+impl Invert {
+    pub fn new(inner: impl TexPtr) -> Self {
+        Self {
+            inner: inner.into(),
+        }
+    }
+
+    fn eval(&self, q: &Query) -> Vec3 {
+        1. - q.eval(self.inner.as_ref())
+    }
+}
+
+texture_from_query!(Invert);
+
+// shifts the hue by a fraction of the colour wheel and scales saturation and value
+pub struct Hsv {
+    inner: Arc<dyn Texture>,
+    hue: f64,
+    saturation: f64,
+    value: f64,
+}
+
+impl Hsv {
+    pub fn new(inner: impl TexPtr) -> Self {
+        Self {
+            inner: inner.into(),
+            hue: 0.,
+            saturation: 1.,
+            value: 1.,
+        }
+    }
+
+    pub fn hue(self, hue: f64) -> Self {
+        Self { hue, ..self }
+    }
+
+    pub fn saturation(self, saturation: f64) -> Self {
+        Self { saturation, ..self }
+    }
+
+    pub fn value(self, value: f64) -> Self {
+        Self { value, ..self }
+    }
+
+    fn eval(&self, q: &Query) -> Vec3 {
+        let [h, s, v] = rgb_to_hsv(&q.eval(self.inner.as_ref()));
+        hsv_to_rgb(
+            (h + self.hue).rem_euclid(1.),
+            (s * self.saturation).clamp(0., 1.),
+            (v * self.value).max(0.),
+        )
+    }
+}
+
+texture_from_query!(Hsv);
+
+// hue in turns
+fn rgb_to_hsv(c: &Vec3) -> [f64; 3] {
+    let max = c.x().max(c.y()).max(c.z());
+    let min = c.x().min(c.y()).min(c.z());
+    let delta = max - min;
+    if max <= 0. {
+        return [0., 0., 0.];
+    }
+
+    let hue = if delta <= 0. {
+        0.
+    } else if max == c.x() {
+        ((c.y() - c.z()) / delta).rem_euclid(6.)
+    } else if max == c.y() {
+        (c.z() - c.x()) / delta + 2.
+    } else {
+        (c.x() - c.y()) / delta + 4.
+    };
+    [hue / 6., delta / max, max]
+}
+
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> Vec3 {
+    let channel = |n: f64| {
+        let k = (n + 6. * h).rem_euclid(6.);
+        v - v * s * k.min(4. - k).clamp(0., 1.)
+    };
+    vec3(channel(5.), channel(3.), channel(1.))
+}
+
+// looks the inner texture up at scaled, then rotated, then offset texture coordinates
+pub struct UvTransform {
+    inner: Arc<dyn Texture>,
+    scale: Vec2,
+    rotation: f64,
+    offset: Vec2,
+}
+
+impl UvTransform {
+    pub fn new(inner: impl TexPtr) -> Self {
+        Self {
+            inner: inner.into(),
+            scale: vec2(1., 1.),
+            rotation: 0.,
+            offset: Vec2::zero(),
+        }
+    }
+
+    pub fn scale(self, scale: Vec2) -> Self {
+        Self { scale, ..self }
+    }
+
+    // counter-clockwise, in degrees
+    pub fn rotate(self, degrees: f64) -> Self {
+        Self {
+            rotation: degrees.to_radians(),
+            ..self
+        }
+    }
+
+    pub fn offset(self, offset: Vec2) -> Self {
+        Self { offset, ..self }
+    }
+
+    fn linear(&self, uv: Vec2) -> Vec2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (u, v) = (self.scale.u() * uv.u(), self.scale.v() * uv.v());
+        vec2(cos * u - sin * v, sin * u + cos * v)
+    }
+
+    fn eval(&self, q: &Query) -> Vec3 {
+        let footprint = q.footprint();
+        let uv = self.linear(q.uv()) + self.offset;
+        let q = q.with_uv(
+            uv,
+            self.linear(footprint.duvdx),
+            self.linear(footprint.duvdy),
+        );
+        q.eval(self.inner.as_ref())
+    }
+}
+
+texture_from_query!(UvTransform);
+
+// projects the inner texture along the three axes and blends the projections by how squarely
+// the surface faces each of them. Needs no texture coordinates, which suits meshes without any.
+pub struct Triplanar {
+    inner: Arc<dyn Texture>,
+    scale: f64,
+    sharpness: f64,
+}
+
+impl Triplanar {
+    pub fn new(inner: impl TexPtr) -> Self {
+        Self {
+            inner: inner.into(),
+            scale: 1.,
+            sharpness: 4.,
+        }
+    }
+
+    // texture repeats per unit
+    pub fn scale(self, scale: f64) -> Self {
+        Self { scale, ..self }
+    }
+
+    // higher values narrow the seams where the projections blend
+    pub fn sharpness(self, sharpness: f64) -> Self {
+        Self { sharpness, ..self }
+    }
+
+    // lookups without a surface blend all three evenly
+    fn eval(&self, q: &Query) -> Vec3 {
+        let weights = match q.normal() {
+            Some(n) => n.map(|c| c.abs().powf(self.sharpness)),
+            None => Vec3::new1(1.),
+        };
+        let total = weights.x() + weights.y() + weights.z();
+
+        let p = self.scale * q.p();
+        let footprint = q.footprint();
+        let (dpdx, dpdy) = (self.scale * footprint.dpdx, self.scale * footprint.dpdy);
+        let planes = [(1, 2), (0, 2), (0, 1)];
+
+        (0..3)
+            .filter(|a| weights[*a] > 0.)
+            .map(|a| {
+                let (u, v) = planes[a];
+                let project = |d: &Vec3| vec2(d[u], d[v]);
+                let q = q.with_uv(project(&p), project(&dpdx), project(&dpdy));
+                weights[a] / total * q.eval(self.inner.as_ref())
+            })
+            .sum()
+    }
+}
+
+texture_from_query!(Triplanar);
+
+#[derive(Debug, Clone, Copy)]
+enum Space {
+    World,
+    // on the shape, before it was translated or rotated into place
+    Object,
+}
+
+// feeds the inner texture a scaled, then rotated, then offset position, in world or object space.
+// Object space keeps a texture stuck to its shape when the shape is moved around.
+pub struct Mapping {
+    inner: Arc<dyn Texture>,
+    space: Space,
+    scale: Vec3,
+    axis: Vec3,
+    angle: f64,
+    offset: Vec3,
+}
+
+impl Mapping {
+    pub fn world(inner: impl TexPtr) -> Self {
+        Self {
+            inner: inner.into(),
+            space: Space::World,
+            scale: vec3(1, 1, 1),
+            axis: vec3(0, 1, 0),
+            angle: 0.,
+            offset: Vec3::zero(),
+        }
+    }
+
+    pub fn object(inner: impl TexPtr) -> Self {
+        Self {
+            space: Space::Object,
+            ..Self::world(inner)
+        }
+    }
+
+    pub fn scale(self, scale: Vec3) -> Self {
+        Self { scale, ..self }
+    }
+
+    // counter-clockwise around `axis`, in degrees
+    pub fn rotate(self, axis: Vec3, degrees: f64) -> Self {
+        Self {
+            axis: axis.normalize(),
+            angle: degrees.to_radians(),
+            ..self
+        }
+    }
+
+    pub fn offset(self, offset: Vec3) -> Self {
+        Self { offset, ..self }
+    }
+
+    // Rodrigues' rotation of the scaled vector
+    fn linear(&self, v: &Vec3) -> Vec3 {
+        let v = self.scale * v;
+        let (sin, cos) = self.angle.sin_cos();
+        let k = self.axis;
+        cos * v + sin * cross(&k, &v) + (1. - cos) * dot(&k, &v) * k
+    }
+
+    fn eval(&self, q: &Query) -> Vec3 {
+        let p = match self.space {
+            Space::World => q.p(),
+            Space::Object => q.local_p(),
+        };
+        let footprint = q.footprint();
+        let q = q.with_p(
+            self.linear(&p) + self.offset,
+            self.linear(&footprint.dpdx),
+            self.linear(&footprint.dpdy),
+        );
+        q.eval(self.inner.as_ref())
+    }
+}
+
+texture_from_query!(Mapping);
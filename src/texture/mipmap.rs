@@ -0,0 +1,210 @@
+use std::{io, path::Path};
+
+use crate::{
+    hit::Footprint,
+    image::Image,
+    math::{vec2, Vec2, Vec3},
+};
+
+use super::Texture;
+
+// footprints more elongated than this are widened, which blurs them a little but bounds the
+// number of texels an ewa lookup visits
+const MAX_ANISOTROPY: f64 = 8.;
+
+// falloff of the gaussian the ewa filter weights texels with
+const EWA_ALPHA: f64 = 2.;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Filter {
+    // bilinear on the full resolution image, footprints are ignored
+    Bilinear,
+    // bilinear on the two levels around the footprint's width
+    Trilinear,
+    // elliptically weighted average over the footprint, on the level of its minor axis
+    Ewa,
+}
+
+// image wrapped around the texture coordinates, repeating outside of [0, 1]
+pub struct ImageTexture {
+    // full resolution first, halved down to a single texel
+    levels: Vec<Image>,
+    filter: Filter,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image) -> Self {
+        let mut levels = vec![image];
+        loop {
+            let last = levels.last().unwrap();
+            if last.width() == 1 && last.height() == 1 {
+                break;
+            }
+            let next = downsample(last);
+            levels.push(next);
+        }
+
+        Self {
+            levels,
+            filter: Filter::Ewa,
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(Image::open(path)?))
+    }
+
+    pub fn filter(self, filter: Filter) -> Self {
+        Self { filter, ..self }
+    }
+
+    fn texel(&self, level: usize, x: isize, y: isize) -> Vec3 {
+        let image = &self.levels[level];
+        let x = x.rem_euclid(image.width() as isize) as usize;
+        let y = y.rem_euclid(image.height() as isize) as usize;
+        image.get(x, y)
+    }
+
+    // continuous texel coordinates with v pointing up, texel centres sit at half integers
+    fn texel_coords(&self, level: usize, uv: Vec2) -> (f64, f64) {
+        let image = &self.levels[level];
+        (
+            uv.u() * image.width() as f64 - 0.5,
+            (1. - uv.v()) * image.height() as f64 - 0.5,
+        )
+    }
+
+    fn bilinear(&self, level: usize, uv: Vec2) -> Vec3 {
+        let (s, t) = self.texel_coords(level, uv);
+        let (x, y) = (s.floor(), t.floor());
+        let (ds, dt) = (s - x, t - y);
+        let (x, y) = (x as isize, y as isize);
+
+        (1. - ds) * (1. - dt) * self.texel(level, x, y)
+            + ds * (1. - dt) * self.texel(level, x + 1, y)
+            + (1. - ds) * dt * self.texel(level, x, y + 1)
+            + ds * dt * self.texel(level, x + 1, y + 1)
+    }
+
+    // level on which a texel is `width` wide in texture coordinates, fractional between two
+    fn level(&self, width: f64) -> f64 {
+        let image = &self.levels[0];
+        let resolution = image.width().max(image.height()) as f64;
+        let level = (width * resolution).max(1e-8).log2();
+        level.clamp(0., (self.levels.len() - 1) as f64)
+    }
+
+    fn between_levels(&self, level: f64, lookup: impl Fn(usize) -> Vec3) -> Vec3 {
+        let lower = level.floor() as usize;
+        if lower + 1 >= self.levels.len() {
+            return lookup(lower);
+        }
+        let frac = level - lower as f64;
+        (1. - frac) * lookup(lower) + frac * lookup(lower + 1)
+    }
+
+    fn trilinear(&self, uv: Vec2, footprint: &Footprint) -> Vec3 {
+        let width = [footprint.duvdx, footprint.duvdy]
+            .iter()
+            .map(|d| d.u().abs().max(d.v().abs()))
+            .fold(0., f64::max);
+        self.between_levels(self.level(width), |level| self.bilinear(level, uv))
+    }
+
+    fn ewa(&self, uv: Vec2, footprint: &Footprint) -> Vec3 {
+        let (mut major, mut minor) = (footprint.duvdx, footprint.duvdy);
+        if major.length_squared() < minor.length_squared() {
+            std::mem::swap(&mut major, &mut minor);
+        }
+        let major_length = major.length();
+        let mut minor_length = minor.length();
+
+        if minor_length * MAX_ANISOTROPY < major_length && minor_length > 0. {
+            let scale = major_length / (minor_length * MAX_ANISOTROPY);
+            minor *= scale;
+            minor_length *= scale;
+        }
+        if minor_length == 0. {
+            return self.bilinear(0, uv);
+        }
+
+        self.between_levels(self.level(minor_length), |level| {
+            self.ewa_level(level, uv, major, minor)
+        })
+    }
+
+    // gaussian weights over the ellipse spanned by the two axes, as in Heckbert's thesis
+    fn ewa_level(&self, level: usize, uv: Vec2, axis0: Vec2, axis1: Vec2) -> Vec3 {
+        // the coarsest level is a single texel, there is nothing left to filter
+        if level >= self.levels.len() - 1 {
+            return self.texel(self.levels.len() - 1, 0, 0);
+        }
+        let image = &self.levels[level];
+        let (s, t) = self.texel_coords(level, uv);
+        let scale = |d: Vec2| vec2(d.u() * image.width() as f64, -d.v() * image.height() as f64);
+        let (d0, d1) = (scale(axis0), scale(axis1));
+
+        // implicit ellipse a s² + b s t + c t² < 1, grown by a texel so that it always covers one
+        let a = d0.y() * d0.y() + d1.y() * d1.y() + 1.;
+        let b = -2. * (d0.x() * d0.y() + d1.x() * d1.y());
+        let c = d0.x() * d0.x() + d1.x() * d1.x() + 1.;
+        let f = a * c - b * b / 4.;
+        let (a, b, c) = (a / f, b / f, c / f);
+
+        let det = 4. * a * c - b * b;
+        // near degenerate ellipses would stretch over the whole texture many times, the texels
+        // wrap around so a level's size in each direction is as far as it needs to reach
+        let s_extent = (2. * (det * c).sqrt() / det).min(image.width() as f64);
+        let t_extent = (2. * (det * a).sqrt() / det).min(image.height() as f64);
+
+        let mut sum = Vec3::zero();
+        let mut weights = 0.;
+        for y in (t - t_extent).ceil() as isize..=(t + t_extent).floor() as isize {
+            let tt = y as f64 - t;
+            for x in (s - s_extent).ceil() as isize..=(s + s_extent).floor() as isize {
+                let ss = x as f64 - s;
+                let r2 = a * ss * ss + b * ss * tt + c * tt * tt;
+                if r2 < 1. {
+                    let weight = (-EWA_ALPHA * r2).exp() - (-EWA_ALPHA).exp();
+                    sum += weight * self.texel(level, x, y);
+                    weights += weight;
+                }
+            }
+        }
+
+        if weights > 0. {
+            sum / weights
+        } else {
+            self.bilinear(level, uv)
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, uv: Vec2, _p: &Vec3) -> Vec3 {
+        self.bilinear(0, uv)
+    }
+
+    fn filtered(&self, uv: Vec2, _p: &Vec3, footprint: &Footprint) -> Vec3 {
+        match self.filter {
+            Filter::Bilinear => self.bilinear(0, uv),
+            Filter::Trilinear => self.trilinear(uv, footprint),
+            Filter::Ewa => self.ewa(uv, footprint),
+        }
+    }
+}
+
+// box filters 2x2 blocks, the last row and column of odd sized images are repeated
+fn downsample(image: &Image) -> Image {
+    let width = image.width().div_ceil(2);
+    let height = image.height().div_ceil(2);
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (x, y) = (2 * x, 2 * y);
+            (image.get(x, y) + image.get(x + 1, y) + image.get(x, y + 1) + image.get(x + 1, y + 1))
+                / 4.
+        })
+        .collect();
+    Image::new(width, height, pixels)
+}
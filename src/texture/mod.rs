@@ -1,13 +1,45 @@
 use std::sync::Arc;
 
-use crate::math::{Vec2, Vec3};
+use crate::{
+    hit::{Footprint, HitRecord},
+    math::{Vec2, Vec3},
+};
 
+// first, so that the other modules see its macro
+#[macro_use]
+mod query;
+
+mod cellular;
+mod checker;
+mod combine;
+mod mipmap;
+mod noise;
+mod parse;
 mod perlin;
+mod ramp;
 
-pub use perlin::Noise;
+pub use cellular::{Feature, Metric, Worley};
+pub use checker::Checker;
+pub use combine::{Arithmetic, Hsv, Invert, Mapping, Mix, Operator, Triplanar, UvTransform};
+pub use mipmap::{Filter, ImageTexture};
+pub use noise::{NoiseBasis, Perlin, Simplex};
+pub use parse::{open, parse};
+pub use perlin::{Fbm, Marble, Noise, Wood};
+pub use ramp::{ColorRamp, Interpolation};
 
 pub trait Texture: Send + Sync {
     fn value(&self, uv: Vec2, p: &Vec3) -> Vec3;
+
+    // average over the area a pixel sees, textures without detail to lose take a point sample
+    fn filtered(&self, uv: Vec2, p: &Vec3, footprint: &Footprint) -> Vec3 {
+        let _ = footprint;
+        self.value(uv, p)
+    }
+
+    // looked up at a surface, where the normal and the position on the shape are known too
+    fn at_hit(&self, rec: &HitRecord) -> Vec3 {
+        self.filtered(rec.uv, &rec.p, &rec.footprint)
+    }
 }
 
 pub trait TexPtr {
@@ -53,3 +85,41 @@ impl Texture for Constant {
         self.color
     }
 }
+
+pub trait TextureExt: Sized {
+    fn mix(self, other: impl TexPtr, factor: impl TexPtr) -> Mix;
+    fn add(self, other: impl TexPtr) -> Arithmetic;
+    fn subtract(self, other: impl TexPtr) -> Arithmetic;
+    fn multiply(self, other: impl TexPtr) -> Arithmetic;
+    fn invert(self) -> Invert;
+    fn ramp(self, stops: Vec<(f64, Vec3)>) -> ColorRamp;
+}
+
+impl<T> TextureExt for T
+where
+    T: TexPtr,
+{
+    fn mix(self, other: impl TexPtr, factor: impl TexPtr) -> Mix {
+        Mix::new(self, other, factor)
+    }
+
+    fn add(self, other: impl TexPtr) -> Arithmetic {
+        Arithmetic::new(Operator::Add, self, other)
+    }
+
+    fn subtract(self, other: impl TexPtr) -> Arithmetic {
+        Arithmetic::new(Operator::Subtract, self, other)
+    }
+
+    fn multiply(self, other: impl TexPtr) -> Arithmetic {
+        Arithmetic::new(Operator::Multiply, self, other)
+    }
+
+    fn invert(self) -> Invert {
+        Invert::new(self)
+    }
+
+    fn ramp(self, stops: Vec<(f64, Vec3)>) -> ColorRamp {
+        ColorRamp::new(self, stops)
+    }
+}
@@ -0,0 +1,224 @@
+// Gradient noise generators. Each one is built from a seed, so a texture owns its own field and
+// renders the same on every run.
+
+use itertools::iproduct;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::math::{dot, vec3, Vec3};
+
+pub trait NoiseBasis: Send + Sync {
+    // roughly in [-1, 1], zero on the integer lattice
+    fn noise(&self, p: &Vec3) -> f64;
+
+    // with a fourth coordinate, usually time, along which the field changes smoothly
+    fn noise4(&self, p: &Vec3, w: f64) -> f64;
+}
+
+// Perlin's lattice noise with random gradients and hermite blending
+pub struct Perlin {
+    ranvec: Box<[Vec3]>,
+    ranvec4: Box<[[f64; 4]]>,
+    perm_x: Box<[usize]>,
+    perm_y: Box<[usize]>,
+    perm_z: Box<[usize]>,
+    perm_w: Box<[usize]>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        Self {
+            ranvec: (0..256)
+                .map(|_| {
+                    (-1. + 2. * vec3(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>()))
+                        .normalize()
+                })
+                .collect(),
+            ranvec4: (0..256)
+                .map(|_| {
+                    let g = [(); 4].map(|_| rng.gen_range(-1. ..1.));
+                    let length = g.iter().map(|c| c * c).sum::<f64>().sqrt();
+                    g.map(|c| c / length)
+                })
+                .collect(),
+            perm_x: perlin_generate_perm(&mut rng),
+            perm_y: perlin_generate_perm(&mut rng),
+            perm_z: perlin_generate_perm(&mut rng),
+            perm_w: perlin_generate_perm(&mut rng),
+        }
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl NoiseBasis for Perlin {
+    fn noise(&self, p: &Vec3) -> f64 {
+        let floored = p.map(f64::floor);
+        let uvw = *p - floored;
+
+        let i = floored.x() as i32;
+        let j = floored.y() as i32;
+        let k = floored.z() as i32;
+
+        let mut c: [[[Vec3; 2]; 2]; 2] = Default::default();
+        iproduct!(0..2, 0..2, 0..2).for_each(|(di, dj, dk)| {
+            c[di][dj][dk] = self.ranvec[self.perm_x[((i + di as i32) & 255) as usize]
+                ^ self.perm_y[((j + dj as i32) & 255) as usize]
+                ^ self.perm_z[((k + dk as i32) & 255) as usize]];
+        });
+
+        perlin_interp(c, uvw)
+    }
+
+    // the same blend over the sixteen corners of a hypercube
+    fn noise4(&self, p: &Vec3, w: f64) -> f64 {
+        let q = [p.x(), p.y(), p.z(), w];
+        let floored = q.map(f64::floor);
+        let f = [0, 1, 2, 3].map(|a| q[a] - floored[a]);
+        let smooth = f.map(|t| t * t * (3. - 2. * t));
+        let perms = [&self.perm_x, &self.perm_y, &self.perm_z, &self.perm_w];
+
+        (0..16)
+            .map(|corner| {
+                let offset = |a: usize| (corner >> a) & 1;
+                let index = (0..4).fold(0, |h, a| {
+                    h ^ perms[a][((floored[a] as i64 + offset(a) as i64) & 255) as usize]
+                });
+                let g = &self.ranvec4[index];
+
+                let weight: f64 = (0..4)
+                    .map(|a| match offset(a) {
+                        0 => 1. - smooth[a],
+                        _ => smooth[a],
+                    })
+                    .product();
+                weight
+                    * (0..4)
+                        .map(|a| g[a] * (f[a] - offset(a) as f64))
+                        .sum::<f64>()
+            })
+            .sum()
+    }
+}
+
+fn perlin_interp(c: [[[Vec3; 2]; 2]; 2], uvw: Vec3) -> f64 {
+    let uuvvww = uvw * uvw * (3. - 2. * uvw);
+
+    iproduct!(0..2, 0..2, 0..2)
+        .map(|(i, j, k)| {
+            let c = c[i][j][k];
+            (vec3(i as f64, j as f64, k as f64), c)
+        })
+        .map(|(ijk, c)| {
+            let weight_v = uvw - ijk;
+            (ijk * uuvvww + (1. - ijk) * (1. - uuvvww)).product() * dot(&c, &weight_v)
+        })
+        .sum()
+}
+
+fn permute(p: &mut [usize], rng: &mut impl Rng) {
+    for i in (1..p.len()).rev() {
+        let target = rng.gen_range(0..i + 1);
+        p.swap(i, target)
+    }
+}
+
+fn perlin_generate_perm(rng: &mut impl Rng) -> Box<[usize]> {
+    let mut p: Box<[usize]> = (0..256).collect();
+    permute(&mut p, rng);
+    p
+}
+
+// Perlin's simplex noise, after Gustavson. Sums kernels over the corners of the simplex around
+// the point rather than blending a whole cube, so it is cheaper in four dimensions and has no
+// axis aligned artifacts.
+pub struct Simplex {
+    perm: Box<[usize]>,
+}
+
+impl Simplex {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            perm: perlin_generate_perm(&mut ChaCha8Rng::seed_from_u64(seed)),
+        }
+    }
+
+    // `skew` maps the simplex grid onto the cubic one, `unskew` back. Gradients point to the
+    // middle of the edges of a hypercube, one coordinate zero and the others ±1.
+    fn simplex<const N: usize>(&self, p: [f64; N], skew: f64, unskew: f64) -> f64 {
+        let s = p.iter().sum::<f64>() * skew;
+        let cell = p.map(|c| (c + s).floor());
+        let t = cell.iter().sum::<f64>() * unskew;
+        let mut x0 = [0.; N];
+        for a in 0..N {
+            x0[a] = p[a] - (cell[a] - t);
+        }
+
+        // the order of the coordinates picks the simplex, and the walk to its far corner
+        let mut rank = [0; N];
+        for a in 0..N {
+            for b in a + 1..N {
+                if x0[a] > x0[b] {
+                    rank[a] += 1;
+                } else {
+                    rank[b] += 1;
+                }
+            }
+        }
+
+        (0..=N)
+            .map(|k| {
+                let offset = |a: usize| (rank[a] + k >= N) as i64;
+                let mut x = [0.; N];
+                for a in 0..N {
+                    x[a] = x0[a] - offset(a) as f64 + k as f64 * unskew;
+                }
+
+                let falloff = 0.6 - x.iter().map(|c| c * c).sum::<f64>();
+                if falloff <= 0. {
+                    return 0.;
+                }
+
+                let hash = (0..N).rev().fold(0, |h, a| {
+                    self.perm[((cell[a] as i64 + offset(a) + h as i64) & 255) as usize]
+                });
+                let zero = hash % N;
+                let gradient: f64 = (0..N)
+                    .filter(|a| *a != zero)
+                    .map(|a| match ((hash / N) >> a) & 1 {
+                        0 => x[a],
+                        _ => -x[a],
+                    })
+                    .sum();
+
+                falloff.powi(4) * gradient
+            })
+            .sum()
+    }
+}
+
+impl Default for Simplex {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl NoiseBasis for Simplex {
+    fn noise(&self, p: &Vec3) -> f64 {
+        32. * self.simplex([p.x(), p.y(), p.z()], 1. / 3., 1. / 6.)
+    }
+
+    fn noise4(&self, p: &Vec3, w: f64) -> f64 {
+        let sqrt5 = 5f64.sqrt();
+        27. * self.simplex(
+            [p.x(), p.y(), p.z(), w],
+            (sqrt5 - 1.) / 4.,
+            (5. - sqrt5) / 20.,
+        )
+    }
+}
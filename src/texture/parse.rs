@@ -0,0 +1,644 @@
+// Texture expressions, so that materials can be put together without recompiling. Functions
+// take positional or named arguments, a number stands for grey, a tuple of three for a colour,
+// and + - * work channel by channel. Everything after a # is a comment.
+//
+//     mix(rgb(0.8, 0.7, 0.6), marble(4, distortion = 6), worley(8, feature = f2_minus_f1))
+//     0.5 * triplanar(image("bricks.hdr"), scale = 2) + (0.1, 0.1, 0.2)
+
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::math::{vec2, vec3, Vec2, Vec3};
+
+use super::{
+    Arithmetic, Checker, ColorRamp, Constant, Fbm, Feature, Filter, Hsv, ImageTexture,
+    Interpolation, Invert, Mapping, Marble, Metric, Mix, Noise, Operator, Perlin, Simplex, Texture,
+    Triplanar, UvTransform, Wood, Worley,
+};
+
+pub fn parse(source: &str) -> io::Result<Arc<dyn Texture>> {
+    Parser::new(source, None)?.texture()
+}
+
+// image paths in the file are relative to the directory it is in
+pub fn open(path: impl AsRef<Path>) -> io::Result<Arc<dyn Texture>> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+    Parser::new(&source, path.parent())?.texture()
+}
+
+fn error(position: usize, msg: impl Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} at byte {}", msg, position),
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Text(String),
+    Symbol(char),
+}
+
+fn tokenize(source: &str) -> io::Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' {
+            while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            let mut previous = c;
+            while let Some((i, c)) = chars.next_if(|(_, c)| {
+                c.is_ascii_alphanumeric()
+                    || *c == '.'
+                    || ((*c == '-' || *c == '+') && (previous == 'e' || previous == 'E'))
+            }) {
+                end = i + c.len_utf8();
+                previous = c;
+            }
+            let number = source[start..end]
+                .parse()
+                .map_err(|_| error(start, "malformed number"))?;
+            tokens.push((start, Token::Number(number)));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some((i, c)) = chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_') {
+                end = i + c.len_utf8();
+            }
+            tokens.push((start, Token::Name(source[start..end].to_string())));
+        } else if c == '"' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, c)) => text.push(c),
+                    None => return Err(error(start, "unterminated string")),
+                }
+            }
+            tokens.push((start, Token::Text(text)));
+        } else if "(),=+-*".contains(c) {
+            chars.next();
+            tokens.push((start, Token::Symbol(c)));
+        } else {
+            return Err(error(start, format!("unexpected character '{}'", c)));
+        }
+    }
+
+    Ok(tokens)
+}
+
+enum Value {
+    Number(f64),
+    Tuple(Vec<f64>),
+    Text(String),
+    Name(String),
+    Texture(Arc<dyn Texture>),
+    // kept apart from other textures, marble and wood take one to drive their noise
+    Fbm(Fbm),
+}
+
+impl Value {
+    fn into_texture(self, position: usize) -> io::Result<Arc<dyn Texture>> {
+        match self {
+            Value::Number(n) => Ok(Arc::new(Constant::new(Vec3::new1(n)))),
+            Value::Tuple(t) if t.len() == 3 => Ok(Arc::new(Constant::new(vec3(t[0], t[1], t[2])))),
+            Value::Texture(texture) => Ok(texture),
+            Value::Fbm(fbm) => Ok(Arc::new(fbm)),
+            _ => Err(error(position, "expected a texture")),
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    end: usize,
+    directory: Option<&'a Path>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &str, directory: Option<&'a Path>) -> io::Result<Self> {
+        Ok(Self {
+            tokens: tokenize(source)?,
+            next: 0,
+            end: source.len(),
+            directory,
+        })
+    }
+
+    fn texture(mut self) -> io::Result<Arc<dyn Texture>> {
+        let value = self.expr()?;
+        if self.next < self.tokens.len() {
+            return Err(error(
+                self.position(),
+                "unexpected input after the expression",
+            ));
+        }
+        value.into_texture(0)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.end, |(p, _)| *p)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, t)| t)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> io::Result<()> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(error(self.position(), format!("expected '{}'", symbol)))
+        }
+    }
+
+    // sums and differences of products
+    fn expr(&mut self) -> io::Result<Value> {
+        let mut value = self.term()?;
+        loop {
+            let position = self.position();
+            let op = if self.eat('+') {
+                Operator::Add
+            } else if self.eat('-') {
+                Operator::Subtract
+            } else {
+                return Ok(value);
+            };
+            let rhs = self.term()?;
+            value = arithmetic(op, value, rhs, position)?;
+        }
+    }
+
+    fn term(&mut self) -> io::Result<Value> {
+        let mut value = self.unary()?;
+        loop {
+            let position = self.position();
+            if !self.eat('*') {
+                return Ok(value);
+            }
+            let rhs = self.unary()?;
+            value = arithmetic(Operator::Multiply, value, rhs, position)?;
+        }
+    }
+
+    fn unary(&mut self) -> io::Result<Value> {
+        let position = self.position();
+        if !self.eat('-') {
+            return self.primary();
+        }
+        match self.unary()? {
+            Value::Number(n) => Ok(Value::Number(-n)),
+            Value::Tuple(t) => Ok(Value::Tuple(t.iter().map(|c| -c).collect())),
+            value => arithmetic(Operator::Subtract, Value::Number(0.), value, position),
+        }
+    }
+
+    fn primary(&mut self) -> io::Result<Value> {
+        let position = self.position();
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| error(position, "unexpected end of input"))?;
+        self.next += 1;
+
+        match token {
+            Token::Number(n) => Ok(Value::Number(n)),
+            Token::Text(text) => Ok(Value::Text(text)),
+            Token::Name(name) if self.eat('(') => {
+                let args = self.args(&name, position)?;
+                self.call(&name, args)
+            }
+            Token::Name(name) => Ok(Value::Name(name)),
+            // a group, or a tuple of numbers
+            Token::Symbol('(') => {
+                let first = self.expr()?;
+                if self.eat(')') {
+                    return Ok(first);
+                }
+                let mut components = vec![number(first, position)?];
+                while self.eat(',') {
+                    let position = self.position();
+                    components.push(number(self.expr()?, position)?);
+                }
+                self.expect(')')?;
+                Ok(Value::Tuple(components))
+            }
+            Token::Symbol(c) => Err(error(position, format!("unexpected '{}'", c))),
+        }
+    }
+
+    // after the opening parenthesis
+    fn args(&mut self, function: &str, position: usize) -> io::Result<Args> {
+        let mut args = Args {
+            function: function.to_string(),
+            position,
+            positional: Vec::new(),
+            named: Vec::new(),
+        };
+        if self.eat(')') {
+            return Ok(args);
+        }
+
+        loop {
+            let position = self.position();
+            let named = match (self.peek(), self.tokens.get(self.next + 1)) {
+                (Some(Token::Name(name)), Some((_, Token::Symbol('=')))) => Some(name.clone()),
+                _ => None,
+            };
+            match named {
+                Some(name) => {
+                    self.next += 2;
+                    args.named.push((name, position, self.expr()?));
+                }
+                None if args.named.is_empty() => {
+                    args.positional.push(Some((position, self.expr()?)))
+                }
+                None => return Err(error(position, "positional argument after a named one")),
+            }
+
+            if self.eat(')') {
+                return Ok(args);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn path(&self, path: String) -> PathBuf {
+        match self.directory {
+            Some(directory) => directory.join(path),
+            None => PathBuf::from(path),
+        }
+    }
+
+    fn call(&self, function: &str, mut args: Args) -> io::Result<Value> {
+        let texture: Arc<dyn Texture> = match function {
+            "rgb" => {
+                let r = args.required_number(0, "r")?;
+                let color = match args.number(1, "g")? {
+                    Some(g) => vec3(r, g, args.required_number(2, "b")?),
+                    None => Vec3::new1(r),
+                };
+                Arc::new(Constant::new(color))
+            }
+            "image" => {
+                let path = self.path(args.text(0, "path")?);
+                let filter = match args.name(1, "filter")?.as_deref() {
+                    None | Some("ewa") => Filter::Ewa,
+                    Some("trilinear") => Filter::Trilinear,
+                    Some("bilinear") => Filter::Bilinear,
+                    Some(other) => return Err(args.invalid("filter", other)),
+                };
+                let image = ImageTexture::open(&path)
+                    .map_err(|e| error(args.position, format!("{}: {}", path.display(), e)))?;
+                Arc::new(image.filter(filter))
+            }
+            "checker" => Arc::new(Checker::new(
+                args.texture(0, "even")?,
+                args.texture(1, "odd")?,
+                args.number(2, "scale")?.unwrap_or(1.),
+            )),
+            "checker_uv" => {
+                let (even, odd) = (args.texture(0, "even")?, args.texture(1, "odd")?);
+                let u = args.number(2, "u")?.unwrap_or(1.);
+                let v = args.number(3, "v")?.unwrap_or(u);
+                Arc::new(Checker::uv(even, odd, u, v))
+            }
+            "noise" => {
+                let noise = Noise::new(args.required_number(0, "scale")?);
+                Arc::new(noise.seed(args.seed(1, "seed")?))
+            }
+            "fbm" => {
+                let fbm = self.fbm(&mut args)?;
+                args.finish()?;
+                return Ok(Value::Fbm(fbm));
+            }
+            "marble" => {
+                let mut marble = Marble::new(args.required_number(0, "scale")?);
+                if let Some(axis) = args.vector(1, "axis")? {
+                    marble = marble.axis(axis);
+                }
+                if let Some(distortion) = args.number(2, "distortion")? {
+                    marble = marble.distortion(distortion);
+                }
+                if let Some(turbulence) = args.fbm(3, "turbulence")? {
+                    marble = marble.turbulence(turbulence);
+                }
+                Arc::new(marble)
+            }
+            "wood" => {
+                let mut wood = Wood::new(args.required_number(0, "rings")?);
+                if let Some(center) = args.vector(1, "center")? {
+                    wood = wood.center(center);
+                }
+                if let Some(axis) = args.vector(2, "axis")? {
+                    wood = wood.axis(axis);
+                }
+                if let Some(distortion) = args.number(3, "distortion")? {
+                    wood = wood.distortion(distortion);
+                }
+                if let Some(noise) = args.fbm(4, "noise")? {
+                    wood = wood.noise(noise);
+                }
+                Arc::new(wood)
+            }
+            "worley" => {
+                let mut worley = Worley::new(args.required_number(0, "scale")?);
+                if let Some(jitter) = args.number(1, "jitter")? {
+                    worley = worley.jitter(jitter);
+                }
+                worley = worley.metric(match args.name(2, "metric")?.as_deref() {
+                    None | Some("euclidean") => Metric::Euclidean,
+                    Some("manhattan") => Metric::Manhattan,
+                    Some("chebyshev") => Metric::Chebyshev,
+                    Some(other) => return Err(args.invalid("metric", other)),
+                });
+                worley = worley.feature(match args.name(3, "feature")?.as_deref() {
+                    None | Some("f1") => Feature::F1,
+                    Some("f2") => Feature::F2,
+                    Some("f2_minus_f1") => Feature::F2MinusF1,
+                    Some(other) => return Err(args.invalid("feature", other)),
+                });
+                Arc::new(worley.seed(args.seed(4, "seed")?))
+            }
+            "mix" => Arc::new(Mix::new(
+                args.texture(0, "a")?,
+                args.texture(1, "b")?,
+                args.texture(2, "factor")?,
+            )),
+            "invert" => Arc::new(Invert::new(args.texture(0, "texture")?)),
+            // the input, then alternating positions and colours
+            "ramp" => {
+                let input = args.texture(0, "input")?;
+                let interpolation = match args.name(usize::MAX, "interpolation")?.as_deref() {
+                    None | Some("linear") => Interpolation::Linear,
+                    Some("constant") => Interpolation::Constant,
+                    Some("smooth") => Interpolation::Smooth,
+                    Some(other) => return Err(args.invalid("interpolation", other)),
+                };
+                let mut stops = Vec::new();
+                let mut i = 1;
+                while i < args.positional.len() {
+                    let position = args.required_number(i, "position")?;
+                    let color = args
+                        .vector(i + 1, "color")?
+                        .ok_or_else(|| args.missing("color"))?;
+                    stops.push((position, color));
+                    i += 2;
+                }
+                if stops.is_empty() {
+                    return Err(args.missing("stops"));
+                }
+                Arc::new(ColorRamp::new(input, stops).interpolation(interpolation))
+            }
+            "hsv" => {
+                let mut hsv = Hsv::new(args.texture(0, "texture")?);
+                if let Some(hue) = args.number(1, "hue")? {
+                    hsv = hsv.hue(hue);
+                }
+                if let Some(saturation) = args.number(2, "saturation")? {
+                    hsv = hsv.saturation(saturation);
+                }
+                if let Some(value) = args.number(3, "value")? {
+                    hsv = hsv.value(value);
+                }
+                Arc::new(hsv)
+            }
+            "uv" => {
+                let mut uv = UvTransform::new(args.texture(0, "texture")?);
+                if let Some(scale) = args.vector2(1, "scale")? {
+                    uv = uv.scale(scale);
+                }
+                if let Some(degrees) = args.number(2, "rotate")? {
+                    uv = uv.rotate(degrees);
+                }
+                if let Some(offset) = args.vector2(3, "offset")? {
+                    uv = uv.offset(offset);
+                }
+                Arc::new(uv)
+            }
+            "triplanar" => {
+                let mut triplanar = Triplanar::new(args.texture(0, "texture")?);
+                if let Some(scale) = args.number(1, "scale")? {
+                    triplanar = triplanar.scale(scale);
+                }
+                if let Some(sharpness) = args.number(2, "sharpness")? {
+                    triplanar = triplanar.sharpness(sharpness);
+                }
+                Arc::new(triplanar)
+            }
+            "world" | "object" => {
+                let inner = args.texture(0, "texture")?;
+                let mut mapping = if function == "world" {
+                    Mapping::world(inner)
+                } else {
+                    Mapping::object(inner)
+                };
+                if let Some(scale) = args.vector(1, "scale")? {
+                    mapping = mapping.scale(scale);
+                }
+                if let Some(degrees) = args.number(2, "angle")? {
+                    let axis = args.vector(3, "axis")?.unwrap_or_else(|| vec3(0, 1, 0));
+                    mapping = mapping.rotate(axis, degrees);
+                }
+                if let Some(offset) = args.vector(4, "offset")? {
+                    mapping = mapping.offset(offset);
+                }
+                Arc::new(mapping)
+            }
+            _ => {
+                return Err(error(
+                    args.position,
+                    format!("unknown function '{}'", function),
+                ))
+            }
+        };
+
+        args.finish()?;
+        Ok(Value::Texture(texture))
+    }
+
+    fn fbm(&self, args: &mut Args) -> io::Result<Fbm> {
+        let mut fbm = Fbm::new(args.required_number(0, "scale")?);
+        if let Some(octaves) = args.number(1, "octaves")? {
+            fbm = fbm.octaves(octaves.max(0.) as usize);
+        }
+        if let Some(lacunarity) = args.number(2, "lacunarity")? {
+            fbm = fbm.lacunarity(lacunarity);
+        }
+        if let Some(gain) = args.number(3, "gain")? {
+            fbm = fbm.gain(gain);
+        }
+        if args.number(4, "turbulence")?.unwrap_or(0.) != 0. {
+            fbm = fbm.turbulence();
+        }
+        let seed = args.seed(5, "seed")?;
+        fbm = match args.name(6, "basis")?.as_deref() {
+            None | Some("perlin") => fbm.basis(Perlin::new(seed)),
+            Some("simplex") => fbm.basis(Simplex::new(seed)),
+            Some(other) => return Err(args.invalid("basis", other)),
+        };
+        if let Some(time) = args.number(7, "time")? {
+            fbm = fbm.time(time);
+        }
+        Ok(fbm)
+    }
+}
+
+fn number(value: Value, position: usize) -> io::Result<f64> {
+    match value {
+        Value::Number(n) => Ok(n),
+        _ => Err(error(position, "expected a number")),
+    }
+}
+
+// numbers are folded, anything else becomes a texture
+fn arithmetic(op: Operator, a: Value, b: Value, position: usize) -> io::Result<Value> {
+    if let (Value::Number(a), Value::Number(b)) = (&a, &b) {
+        return Ok(Value::Number(match op {
+            Operator::Add => a + b,
+            Operator::Subtract => a - b,
+            Operator::Multiply => a * b,
+        }));
+    }
+    Ok(Value::Texture(Arc::new(Arithmetic::new(
+        op,
+        a.into_texture(position)?,
+        b.into_texture(position)?,
+    ))))
+}
+
+struct Args {
+    function: String,
+    position: usize,
+    positional: Vec<Option<(usize, Value)>>,
+    named: Vec<(String, usize, Value)>,
+}
+
+impl Args {
+    // by name, or else at `index` among the positional ones
+    fn take(&mut self, index: usize, name: &str) -> Option<(usize, Value)> {
+        match self.named.iter().position(|(n, _, _)| n == name) {
+            Some(i) => {
+                let (_, position, value) = self.named.remove(i);
+                Some((position, value))
+            }
+            None => self.positional.get_mut(index).and_then(Option::take),
+        }
+    }
+
+    fn missing(&self, name: &str) -> io::Error {
+        error(
+            self.position,
+            format!("{} needs an argument '{}'", self.function, name),
+        )
+    }
+
+    fn invalid(&self, name: &str, value: &str) -> io::Error {
+        error(
+            self.position,
+            format!("{} has no {} '{}'", self.function, name, value),
+        )
+    }
+
+    fn number(&mut self, index: usize, name: &str) -> io::Result<Option<f64>> {
+        self.take(index, name)
+            .map(|(position, value)| number(value, position))
+            .transpose()
+    }
+
+    fn required_number(&mut self, index: usize, name: &str) -> io::Result<f64> {
+        self.number(index, name)?.ok_or_else(|| self.missing(name))
+    }
+
+    fn seed(&mut self, index: usize, name: &str) -> io::Result<u64> {
+        Ok(self.number(index, name)?.unwrap_or(0.).max(0.) as u64)
+    }
+
+    // a tuple of three, or a single number for all of them
+    fn vector(&mut self, index: usize, name: &str) -> io::Result<Option<Vec3>> {
+        match self.take(index, name) {
+            None => Ok(None),
+            Some((_, Value::Number(n))) => Ok(Some(Vec3::new1(n))),
+            Some((_, Value::Tuple(t))) if t.len() == 3 => Ok(Some(vec3(t[0], t[1], t[2]))),
+            Some((position, _)) => Err(error(position, "expected three numbers")),
+        }
+    }
+
+    fn vector2(&mut self, index: usize, name: &str) -> io::Result<Option<Vec2>> {
+        match self.take(index, name) {
+            None => Ok(None),
+            Some((_, Value::Number(n))) => Ok(Some(vec2(n, n))),
+            Some((_, Value::Tuple(t))) if t.len() == 2 => Ok(Some(vec2(t[0], t[1]))),
+            Some((position, _)) => Err(error(position, "expected two numbers")),
+        }
+    }
+
+    fn text(&mut self, index: usize, name: &str) -> io::Result<String> {
+        match self.take(index, name) {
+            None => Err(self.missing(name)),
+            Some((_, Value::Text(text))) => Ok(text),
+            Some((position, _)) => Err(error(position, "expected a string")),
+        }
+    }
+
+    fn name(&mut self, index: usize, name: &str) -> io::Result<Option<String>> {
+        match self.take(index, name) {
+            None => Ok(None),
+            Some((_, Value::Name(name))) => Ok(Some(name)),
+            Some((position, _)) => Err(error(position, "expected a name")),
+        }
+    }
+
+    fn texture(&mut self, index: usize, name: &str) -> io::Result<Arc<dyn Texture>> {
+        match self.take(index, name) {
+            None => Err(self.missing(name)),
+            Some((position, value)) => value.into_texture(position),
+        }
+    }
+
+    fn fbm(&mut self, index: usize, name: &str) -> io::Result<Option<Fbm>> {
+        match self.take(index, name) {
+            None => Ok(None),
+            Some((_, Value::Fbm(fbm))) => Ok(Some(fbm)),
+            Some((position, _)) => Err(error(position, "expected fbm(...)")),
+        }
+    }
+
+    // complains about arguments nothing asked for
+    fn finish(self) -> io::Result<()> {
+        if let Some((position, _)) = self.positional.into_iter().flatten().next() {
+            return Err(error(
+                position,
+                format!("too many arguments to {}", self.function),
+            ));
+        }
+        if let Some((name, position, _)) = self.named.into_iter().next() {
+            return Err(error(
+                position,
+                format!("{} takes no argument '{}'", self.function, name),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -1,132 +1,296 @@
-use itertools::iproduct;
-use once_cell::sync::Lazy;
-use rand::{thread_rng, Rng};
+use std::sync::Arc;
 
-use crate::math::{dot, vec3, Vec2, Vec3};
+use crate::{
+    hit::Footprint,
+    math::{dot, vec3, Vec2, Vec3},
+};
 
-use super::Texture;
+use super::{NoiseBasis, Perlin, Texture};
 
-struct Perlin {
-    // ranfloat: Box<[f64]>,
-    ranvec: Box<[Vec3]>,
-    perm_x: Box<[usize]>,
-    perm_y: Box<[usize]>,
-    perm_z: Box<[usize]>,
+// absolute value of the sum of `depth` octaves. Leaves out the octaves finer than twice the
+// footprint `width`, they would only alias. The noise averages to zero, so that is what they
+// contribute over the footprint. The last octave fades out instead of popping.
+fn turbulence(basis: &dyn NoiseBasis, p: &Vec3, depth: usize, width: f64) -> f64 {
+    let octaves = (-(2. * width).log2() + 1.).clamp(0., depth as f64);
+    let mut p = *p;
+    let mut accum = 0.;
+    let mut weight = 1.0;
+    for i in 0..octaves.ceil() as usize {
+        let fade = (octaves - i as f64).min(1.);
+        accum += fade * weight * basis.noise(&p);
+        weight *= 0.5;
+        p *= 2.;
+    }
+    accum.abs()
+}
+
+// the marble of the book with its constants baked in, `Marble` has them all configurable
+pub struct Noise {
+    scale: f64,
+    perlin: Perlin,
+}
+
+impl Noise {
+    pub fn new(scale: f64) -> Self {
+        Self {
+            scale,
+            perlin: Perlin::default(),
+        }
+    }
+
+    pub fn seed(self, seed: u64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            ..self
+        }
+    }
+
+    fn grey(&self, p: &Vec3, width: f64) -> Vec3 {
+        let turb = turbulence(&self.perlin, p, 7, width);
+        vec3(1., 1., 1.) * 0.5 * (1. + f64::sin(self.scale * p.z() + 10. * turb))
+    }
+}
+
+impl Texture for Noise {
+    fn value(&self, _uv: Vec2, p: &Vec3) -> Vec3 {
+        self.grey(p, 0.)
+    }
+
+    fn filtered(&self, _uv: Vec2, p: &Vec3, footprint: &Footprint) -> Vec3 {
+        self.grey(p, footprint.width())
+    }
+}
+
+// sum of octaves of noise, each `lacunarity` times finer and `gain` times weaker than the last.
+// A single octave is plain noise.
+#[derive(Clone)]
+pub struct Fbm {
+    scale: f64,
+    octaves: usize,
+    lacunarity: f64,
+    gain: f64,
+    turbulence: bool,
+    basis: Arc<dyn NoiseBasis>,
+    // fourth coordinate of the noise, animates the field when it changes between frames
+    time: Option<f64>,
 }
 
-impl Perlin {
-    fn noise(&self, p: &Vec3) -> f64 {
-        let floored = p.map(f64::floor);
-        let uvw = *p - floored;
+impl Fbm {
+    pub fn new(scale: f64) -> Self {
+        Self {
+            scale,
+            octaves: 6,
+            lacunarity: 2.,
+            gain: 0.5,
+            turbulence: false,
+            basis: Arc::new(Perlin::default()),
+            time: None,
+        }
+    }
+
+    // Perlin noise from another seed
+    pub fn seed(self, seed: u64) -> Self {
+        self.basis(Perlin::new(seed))
+    }
+
+    pub fn basis(self, basis: impl NoiseBasis + 'static) -> Self {
+        Self {
+            basis: Arc::new(basis),
+            ..self
+        }
+    }
+
+    pub fn time(self, time: f64) -> Self {
+        Self {
+            time: Some(time),
+            ..self
+        }
+    }
 
-        let i = floored.x() as i32;
-        let j = floored.y() as i32;
-        let k = floored.z() as i32;
+    pub fn octaves(self, octaves: usize) -> Self {
+        Self { octaves, ..self }
+    }
 
-        let mut c: [[[Vec3; 2]; 2]; 2] = Default::default();
-        iproduct!(0..2, 0..2, 0..2).for_each(|(di, dj, dk)| {
-            c[di][dj][dk] = self.ranvec[self.perm_x[((i + di as i32) & 255) as usize]
-                ^ self.perm_y[((j + dj as i32) & 255) as usize]
-                ^ self.perm_z[((k + dk as i32) & 255) as usize]];
-        });
+    pub fn lacunarity(self, lacunarity: f64) -> Self {
+        Self { lacunarity, ..self }
+    }
 
-        perlin_interp(c, uvw)
+    pub fn gain(self, gain: f64) -> Self {
+        Self { gain, ..self }
     }
 
-    fn turb(&self, p: &Vec3, depth: usize) -> f64 {
-        let mut p = *p;
+    // sums the magnitude of every octave, which gives creases where the noise crosses zero
+    pub fn turbulence(self) -> Self {
+        Self {
+            turbulence: true,
+            ..self
+        }
+    }
+
+    // normalized by the total weight of the octaves, so in [-1, 1], or [0, 1] for turbulence.
+    // Octaves finer than the footprint `width` are faded out like in `turbulence`.
+    fn sample(&self, p: &Vec3, width: f64) -> f64 {
+        let width = width * self.scale;
+        let octaves = if width > 0. && self.lacunarity > 1. {
+            (-(2. * width).log2() / self.lacunarity.log2() + 1.).clamp(0., self.octaves as f64)
+        } else {
+            self.octaves as f64
+        };
+
+        let mut p = self.scale * p;
+        let mut time = self.time;
         let mut accum = 0.;
-        let mut weight = 1.0;
-        for _i in 0..depth {
-            accum += weight * self.noise(&p);
-            weight *= 0.5;
-            p *= 2.;
+        let mut total = 0.;
+        let mut weight = 1.;
+        for i in 0..self.octaves {
+            let fade = (octaves - i as f64).clamp(0., 1.);
+            if fade > 0. {
+                let noise = match time {
+                    Some(time) => self.basis.noise4(&p, time),
+                    None => self.basis.noise(&p),
+                };
+                let noise = if self.turbulence { noise.abs() } else { noise };
+                accum += fade * weight * noise;
+            }
+            total += weight;
+            weight *= self.gain;
+            p *= self.lacunarity;
+            time = time.map(|t| t * self.lacunarity);
         }
-        accum.abs()
+
+        if total > 0. {
+            accum / total
+        } else {
+            0.
+        }
+    }
+
+    fn grey(&self, p: &Vec3, width: f64) -> Vec3 {
+        let value = self.sample(p, width);
+        Vec3::new1(if self.turbulence {
+            value
+        } else {
+            0.5 * (1. + value)
+        })
     }
 }
 
-fn perlin_interp(c: [[[Vec3; 2]; 2]; 2], uvw: Vec3) -> f64 {
-    let uuvvww = uvw * uvw * (3. - 2. * uvw);
+impl Texture for Fbm {
+    fn value(&self, _uv: Vec2, p: &Vec3) -> Vec3 {
+        self.grey(p, 0.)
+    }
 
-    iproduct!(0..2, 0..2, 0..2)
-        .map(|(i, j, k)| {
-            let c = c[i][j][k];
-            (vec3(i as f64, j as f64, k as f64), c)
-        })
-        .map(|(ijk, c)| {
-            let weight_v = uvw - ijk;
-            (ijk * uuvvww + (1. - ijk) * (1. - uuvvww)).product() * dot(&c, &weight_v)
-        })
-        .sum()
+    fn filtered(&self, _uv: Vec2, p: &Vec3, footprint: &Footprint) -> Vec3 {
+        self.grey(p, footprint.width())
+    }
 }
 
-#[allow(unused)]
-fn trilinear_interp(c: [[[f64; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
-    iproduct!(0..2, 0..2, 0..2)
-        .map(|(i, j, k)| {
-            let c = c[i][j][k];
-            (i as f64, j as f64, k as f64, c)
-        })
-        .map(|(i, j, k, c)| {
-            c * (i * u + (1. - i) * (1. - u))
-                * (j * v + (1. - j) * (1. - v))
-                * (k * w + (1. - k) * (1. - w))
-        })
-        .sum()
+// bands along `axis`, `scale` radians per unit, pushed around by turbulence
+pub struct Marble {
+    scale: f64,
+    axis: Vec3,
+    distortion: f64,
+    turbulence: Fbm,
 }
 
-// fn perlin_generate() -> Box<[f64]> {
-//     (0..256).map(|_| random()).collect()
-// }
+impl Marble {
+    pub fn new(scale: f64) -> Self {
+        Self {
+            scale,
+            axis: vec3(0, 0, 1),
+            distortion: 10.,
+            turbulence: Fbm::new(1.).octaves(7).turbulence(),
+        }
+    }
 
-fn perlin_generate() -> Box<[Vec3]> {
-    let mut rng = thread_rng();
+    pub fn axis(self, axis: Vec3) -> Self {
+        Self {
+            axis: axis.normalize(),
+            ..self
+        }
+    }
 
-    (0..256)
-        .map(|_| {
-            (-1. + 2. * vec3(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>())).normalize()
-        })
-        .collect()
+    // phase shift at full turbulence, in radians
+    pub fn distortion(self, distortion: f64) -> Self {
+        Self { distortion, ..self }
+    }
+
+    pub fn turbulence(self, turbulence: Fbm) -> Self {
+        Self { turbulence, ..self }
+    }
+
+    fn grey(&self, p: &Vec3, width: f64) -> Vec3 {
+        let phase =
+            self.scale * dot(p, &self.axis) + self.distortion * self.turbulence.sample(p, width);
+        Vec3::new1(0.5 * (1. + phase.sin()))
+    }
 }
 
-fn permute(p: &mut [usize]) {
-    let mut rng = thread_rng();
-    for i in (1..p.len()).rev() {
-        let target = rng.gen_range(0..i + 1);
-        p.swap(i, target)
+impl Texture for Marble {
+    fn value(&self, _uv: Vec2, p: &Vec3) -> Vec3 {
+        self.grey(p, 0.)
+    }
+
+    fn filtered(&self, _uv: Vec2, p: &Vec3, footprint: &Footprint) -> Vec3 {
+        self.grey(p, footprint.width())
     }
 }
 
-fn perlin_generate_perm() -> Box<[usize]> {
-    let mut p: Box<[usize]> = (0..256).collect();
-    permute(&mut p);
-    p
+// growth rings around a line through `center` along `axis`, a sawtooth in [0, 1) that restarts
+// `rings` times per unit of radius. Noise displaces the radius so the rings wobble.
+pub struct Wood {
+    rings: f64,
+    center: Vec3,
+    axis: Vec3,
+    distortion: f64,
+    noise: Fbm,
 }
 
-static NOISE: Lazy<Perlin> = Lazy::new(|| Perlin {
-    // ranfloat: perlin_generate(),
-    ranvec: perlin_generate(),
-    perm_x: perlin_generate_perm(),
-    perm_y: perlin_generate_perm(),
-    perm_z: perlin_generate_perm(),
-});
+impl Wood {
+    pub fn new(rings: f64) -> Self {
+        Self {
+            rings,
+            center: Vec3::zero(),
+            axis: vec3(0, 1, 0),
+            distortion: 0.1,
+            noise: Fbm::new(1.).octaves(3),
+        }
+    }
 
-pub struct Noise {
-    scale: f64,
-}
+    pub fn center(self, center: Vec3) -> Self {
+        Self { center, ..self }
+    }
 
-impl Noise {
-    pub fn new(scale: f64) -> Self {
-        Self { scale }
+    pub fn axis(self, axis: Vec3) -> Self {
+        Self {
+            axis: axis.normalize(),
+            ..self
+        }
+    }
+
+    // radial displacement at full noise, in world units
+    pub fn distortion(self, distortion: f64) -> Self {
+        Self { distortion, ..self }
+    }
+
+    pub fn noise(self, noise: Fbm) -> Self {
+        Self { noise, ..self }
+    }
+
+    fn grey(&self, p: &Vec3, width: f64) -> Vec3 {
+        let d = p - self.center;
+        let radius = (d - dot(&d, &self.axis) * self.axis).length();
+        let radius = radius + self.distortion * self.noise.sample(p, width);
+        Vec3::new1((self.rings * radius).rem_euclid(1.))
     }
 }
 
-impl Texture for Noise {
+impl Texture for Wood {
     fn value(&self, _uv: Vec2, p: &Vec3) -> Vec3 {
-        // vec3(1., 1., 1.) * 0.5 * (1. + NOISE.noise(self.scale * p))
-        // vec3(1., 1., 1.) * NOISE.turb(self.scale * p, 7)
+        self.grey(p, 0.)
+    }
 
-        vec3(1., 1., 1.) * 0.5 * (1. + f64::sin(self.scale * p.z() + 10. * NOISE.turb(p, 7)))
+    fn filtered(&self, _uv: Vec2, p: &Vec3, footprint: &Footprint) -> Vec3 {
+        self.grey(p, footprint.width())
     }
 }
@@ -0,0 +1,124 @@
+use crate::{
+    hit::{Footprint, HitRecord},
+    math::{Vec2, Vec3},
+};
+
+use super::Texture;
+
+// a lookup the way a texture received it, so that combinators ask their inputs the same way
+pub(super) enum Query<'a> {
+    Point(Vec2, Vec3),
+    Filtered(Vec2, Vec3, Footprint),
+    Hit(HitRecord<'a>),
+}
+
+impl<'a> Query<'a> {
+    pub(super) fn hit(rec: &HitRecord<'a>) -> Self {
+        Query::Hit(HitRecord { ..*rec })
+    }
+
+    pub(super) fn eval(&self, texture: &dyn Texture) -> Vec3 {
+        match self {
+            Query::Point(uv, p) => texture.value(*uv, p),
+            Query::Filtered(uv, p, footprint) => texture.filtered(*uv, p, footprint),
+            Query::Hit(rec) => texture.at_hit(rec),
+        }
+    }
+
+    pub(super) fn uv(&self) -> Vec2 {
+        match self {
+            Query::Point(uv, _) | Query::Filtered(uv, _, _) => *uv,
+            Query::Hit(rec) => rec.uv,
+        }
+    }
+
+    pub(super) fn p(&self) -> Vec3 {
+        match self {
+            Query::Point(_, p) | Query::Filtered(_, p, _) => *p,
+            Query::Hit(rec) => rec.p,
+        }
+    }
+
+    pub(super) fn footprint(&self) -> Footprint {
+        match self {
+            Query::Point(_, _) => Footprint::default(),
+            Query::Filtered(_, _, footprint) => *footprint,
+            Query::Hit(rec) => rec.footprint,
+        }
+    }
+
+    // on the outside of the surface, where there is one
+    pub(super) fn normal(&self) -> Option<Vec3> {
+        match self {
+            Query::Hit(rec) => Some(rec.outward_normal()),
+            _ => None,
+        }
+    }
+
+    pub(super) fn local_p(&self) -> Vec3 {
+        match self {
+            Query::Hit(rec) => rec.local_p,
+            _ => self.p(),
+        }
+    }
+
+    // the same lookup at other texture coordinates, `duvdx` and `duvdy` replace the footprint's
+    pub(super) fn with_uv(&self, uv: Vec2, duvdx: Vec2, duvdy: Vec2) -> Self {
+        let footprint = Footprint {
+            duvdx,
+            duvdy,
+            ..self.footprint()
+        };
+        match self {
+            Query::Point(_, p) => Query::Point(uv, *p),
+            Query::Filtered(_, p, _) => Query::Filtered(uv, *p, footprint),
+            Query::Hit(rec) => Query::Hit(HitRecord {
+                uv,
+                footprint,
+                ..*rec
+            }),
+        }
+    }
+
+    pub(super) fn with_p(&self, p: Vec3, dpdx: Vec3, dpdy: Vec3) -> Self {
+        let footprint = Footprint {
+            dpdx,
+            dpdy,
+            ..self.footprint()
+        };
+        match self {
+            Query::Point(uv, _) => Query::Point(*uv, p),
+            Query::Filtered(uv, _, _) => Query::Filtered(*uv, p, footprint),
+            Query::Hit(rec) => Query::Hit(HitRecord {
+                p,
+                footprint,
+                ..*rec
+            }),
+        }
+    }
+}
+
+// implements `Texture` for a type with an `eval(&self, &Query) -> Vec3` that answers all three
+// kinds of lookup
+macro_rules! texture_from_query {
+    ($t:ty) => {
+        impl $crate::texture::Texture for $t {
+            fn value(&self, uv: $crate::math::Vec2, p: &$crate::math::Vec3) -> $crate::math::Vec3 {
+                self.eval(&$crate::texture::query::Query::Point(uv, *p))
+            }
+
+            fn filtered(
+                &self,
+                uv: $crate::math::Vec2,
+                p: &$crate::math::Vec3,
+                footprint: &$crate::hit::Footprint,
+            ) -> $crate::math::Vec3 {
+                self.eval(&$crate::texture::query::Query::Filtered(uv, *p, *footprint))
+            }
+
+            fn at_hit(&self, rec: &$crate::hit::HitRecord) -> $crate::math::Vec3 {
+                self.eval(&$crate::texture::query::Query::hit(rec))
+            }
+        }
+    };
+}
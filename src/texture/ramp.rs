@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use crate::math::Vec3;
+
+use super::{query::Query, TexPtr, Texture};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Interpolation {
+    // holds each colour up to the next stop
+    Constant,
+    Linear,
+    // smoothstep between stops
+    Smooth,
+}
+
+// maps the mean of a grey texture through colour stops, values outside the stops take the
+// colour of the nearest one
+pub struct ColorRamp {
+    input: Arc<dyn Texture>,
+    stops: Vec<(f64, Vec3)>,
+    interpolation: Interpolation,
+}
+
+impl ColorRamp {
+    pub fn new(input: impl TexPtr, mut stops: Vec<(f64, Vec3)>) -> Self {
+        assert!(!stops.is_empty(), "ramp needs at least one stop");
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        Self {
+            input: input.into(),
+            stops,
+            interpolation: Interpolation::Linear,
+        }
+    }
+
+    pub fn interpolation(self, interpolation: Interpolation) -> Self {
+        Self {
+            interpolation,
+            ..self
+        }
+    }
+
+    fn eval(&self, q: &Query) -> Vec3 {
+        self.lookup(q.eval(self.input.as_ref()).mean())
+    }
+
+    fn lookup(&self, x: f64) -> Vec3 {
+        let next = self.stops.partition_point(|(position, _)| *position <= x);
+        if next == 0 {
+            return self.stops[0].1;
+        }
+        if next == self.stops.len() {
+            return self.stops[next - 1].1;
+        }
+
+        let (x0, c0) = self.stops[next - 1];
+        let (x1, c1) = self.stops[next];
+        let t = (x - x0) / (x1 - x0);
+        let t = match self.interpolation {
+            Interpolation::Constant => 0.,
+            Interpolation::Linear => t,
+            Interpolation::Smooth => t * t * (3. - 2. * t),
+        };
+        (1. - t) * c0 + t * c1
+    }
+}
+
+texture_from_query!(ColorRamp);
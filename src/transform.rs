@@ -3,8 +3,10 @@ use std::sync::Arc;
 use itertools::iproduct;
 
 use crate::{
-    hit::{Aabb, HitRecord, Hitable, Ray},
-    math::{vec3, Vec3},
+    csg::{Interval, Solid},
+    hit::{Aabb, HitRecord, Hitable, Ray, SurfaceSample},
+    math::{vec3, Vec2, Vec3},
+    texture::{TexPtr, Texture},
 };
 
 pub struct FlipNormals<T>
@@ -36,6 +38,31 @@ where
     fn random(&self, o: &Vec3) -> Vec3 {
         self.inner.random(o)
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        self.inner.sample_surface().map(flip_sample)
+    }
+
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        self.inner.sample_uv(uv).map(flip_sample)
+    }
+
+    fn area(&self) -> f64 {
+        self.inner.area()
+    }
+
+    fn power(&self) -> Vec3 {
+        self.inner.power()
+    }
+
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        self.inner.finite_bounding_box()
+    }
+}
+
+fn flip_sample(mut sample: SurfaceSample) -> SurfaceSample {
+    sample.normal = -sample.normal;
+    sample
 }
 
 pub fn flip_normals<T>(inner: T) -> FlipNormals<T>
@@ -51,6 +78,7 @@ pub trait HitableExt {
     fn translate(self, offset: Vec3) -> Translate<Self>;
     fn rotate_y(self, angle: f64) -> RotateY<Self>;
     fn flip_face(self) -> FlipFace<Self>;
+    fn alpha_mask(self, opacity: impl TexPtr) -> AlphaMask<Self>;
     fn shared(self) -> Arc<dyn Hitable>;
 }
 
@@ -84,6 +112,14 @@ where
     fn flip_face(self) -> FlipFace<Self> {
         FlipFace { ptr: self }
     }
+
+    fn alpha_mask(self, opacity: impl TexPtr) -> AlphaMask<Self> {
+        AlphaMask {
+            opacity: opacity.into(),
+            threshold: 0.5,
+            inner: self,
+        }
+    }
 }
 
 pub struct Translate<T>
@@ -102,7 +138,6 @@ where
         let moved_r = Ray::new(r.origin() - self.offset, *r.direction());
         self.inner.hit(&moved_r, t_min, t_max).map(|mut rec| {
             rec.p += self.offset;
-            rec.set_face_normal(&moved_r, rec.normal);
             rec
         })
     }
@@ -119,6 +154,51 @@ where
     fn random(&self, o: &Vec3) -> Vec3 {
         self.inner.random(o)
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        self.inner.sample_surface().map(|mut sample| {
+            sample.p += self.offset;
+            sample
+        })
+    }
+
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        self.inner.sample_uv(uv).map(|mut sample| {
+            sample.p += self.offset;
+            sample
+        })
+    }
+
+    fn area(&self) -> f64 {
+        self.inner.area()
+    }
+
+    fn power(&self) -> Vec3 {
+        self.inner.power()
+    }
+
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        let bbox = self.inner.finite_bounding_box()?;
+        Some(Aabb::new(
+            bbox.min() + self.offset,
+            bbox.max() + self.offset,
+        ))
+    }
+}
+
+impl<T> Solid for Translate<T>
+where
+    T: Solid + 'static,
+{
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        let moved_r = Ray::new(r.origin() - self.offset, *r.direction());
+        let mut intervals = self.inner.intervals(&moved_r);
+        for interval in &mut intervals {
+            interval.enter.p += self.offset;
+            interval.exit.p += self.offset;
+        }
+        intervals
+    }
 }
 
 pub struct RotateY<T>
@@ -172,11 +252,11 @@ where
     }
 }
 
-impl<T> Hitable for RotateY<T>
+impl<T> RotateY<T>
 where
-    T: Hitable,
+    T: ?Sized,
 {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn rotate_ray(&self, r: &Ray) -> Ray {
         let mut origin = *r.origin();
         let mut direction = *r.direction();
 
@@ -186,22 +266,47 @@ where
         direction[0] = self.cos_theta * r.direction().x() - self.sin_theta * r.direction().z();
         direction[2] = self.sin_theta * r.direction().x() + self.cos_theta * r.direction().z();
 
-        let rotated_ray = Ray::new(origin, direction);
-        self.inner.hit(&rotated_ray, t_min, t_max).map(|mut rec| {
-            let mut p = rec.p;
-            let mut normal = rec.normal;
+        Ray::new(origin, direction)
+    }
 
-            p[0] = self.cos_theta * rec.p.x() + self.sin_theta * rec.p.z();
-            p[2] = -self.sin_theta * rec.p.x() + self.cos_theta * rec.p.z();
+    fn rotate_back<'m>(&self, mut rec: HitRecord<'m>) -> HitRecord<'m> {
+        let rotate = |v: Vec3| {
+            let mut rotated = v;
+            rotated[0] = self.cos_theta * v.x() + self.sin_theta * v.z();
+            rotated[2] = -self.sin_theta * v.x() + self.cos_theta * v.z();
+            rotated
+        };
 
-            normal[0] = self.cos_theta * rec.normal.x() + self.sin_theta * rec.normal.z();
-            normal[2] = -self.sin_theta * rec.normal.x() + self.cos_theta * rec.normal.z();
+        rec.p = rotate(rec.p);
+        rec.normal = rotate(rec.normal);
+        rec.dpdu = rotate(rec.dpdu);
+        rec.dpdv = rotate(rec.dpdv);
 
-            rec.p = p;
-            rec.set_face_normal(&rotated_ray, normal);
+        rec
+    }
 
-            rec
-        })
+    fn rotate_sample<'m>(&self, mut sample: SurfaceSample<'m>) -> SurfaceSample<'m> {
+        let (p, normal) = (sample.p, sample.normal);
+
+        sample.p[0] = self.cos_theta * p.x() + self.sin_theta * p.z();
+        sample.p[2] = -self.sin_theta * p.x() + self.cos_theta * p.z();
+
+        sample.normal[0] = self.cos_theta * normal.x() + self.sin_theta * normal.z();
+        sample.normal[2] = -self.sin_theta * normal.x() + self.cos_theta * normal.z();
+
+        sample
+    }
+}
+
+impl<T> Hitable for RotateY<T>
+where
+    T: Hitable,
+{
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let rotated_ray = self.rotate_ray(r);
+        self.inner
+            .hit(&rotated_ray, t_min, t_max)
+            .map(|rec| self.rotate_back(rec))
     }
 
     fn bounding_box(&self) -> Aabb {
@@ -215,6 +320,43 @@ where
     fn random(&self, o: &Vec3) -> Vec3 {
         self.inner.random(o)
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        self.inner
+            .sample_surface()
+            .map(|sample| self.rotate_sample(sample))
+    }
+
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        self.inner
+            .sample_uv(uv)
+            .map(|sample| self.rotate_sample(sample))
+    }
+
+    fn area(&self) -> f64 {
+        self.inner.area()
+    }
+
+    fn power(&self) -> Vec3 {
+        self.inner.power()
+    }
+}
+
+impl<T> Solid for RotateY<T>
+where
+    T: Solid,
+{
+    fn intervals(&self, r: &Ray) -> Vec<Interval> {
+        let rotated_ray = self.rotate_ray(r);
+        self.inner
+            .intervals(&rotated_ray)
+            .into_iter()
+            .map(|Interval { enter, exit }| Interval {
+                enter: self.rotate_back(enter),
+                exit: self.rotate_back(exit),
+            })
+            .collect()
+    }
 }
 
 pub struct FlipFace<T>
@@ -246,4 +388,113 @@ where
     fn random(&self, o: &Vec3) -> Vec3 {
         self.ptr.random(o)
     }
+
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        self.ptr.sample_surface().map(flip_sample)
+    }
+
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        self.ptr.sample_uv(uv).map(flip_sample)
+    }
+
+    fn area(&self) -> f64 {
+        self.ptr.area()
+    }
+
+    fn power(&self) -> Vec3 {
+        self.ptr.power()
+    }
+
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        self.ptr.finite_bounding_box()
+    }
+}
+
+// cuts out the parts of a shape where the mean of `opacity` is below the threshold, for leaves,
+// fences and decals. Rays, shadow rays included, go on through the cut out parts.
+pub struct AlphaMask<T>
+where
+    T: ?Sized,
+{
+    opacity: Arc<dyn Texture>,
+    threshold: f64,
+    inner: T,
+}
+
+impl<T> AlphaMask<T> {
+    pub fn threshold(self, threshold: f64) -> Self {
+        Self { threshold, ..self }
+    }
+}
+
+impl<T> AlphaMask<T>
+where
+    T: ?Sized,
+{
+    fn is_opaque(&self, rec: &HitRecord) -> bool {
+        self.opacity.at_hit(rec).mean() >= self.threshold
+    }
+
+    // looked up like a hit from straight above the sample, so both see the same opacity
+    fn is_sample_opaque(&self, sample: &SurfaceSample) -> bool {
+        let r = Ray::new(sample.p + sample.normal, -sample.normal);
+        let rec = HitRecord::new(&r, 1., sample.p, sample.normal, sample.uv, sample.material);
+        self.is_opaque(&rec)
+    }
+}
+
+impl<T> Hitable for AlphaMask<T>
+where
+    T: Hitable,
+{
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut t = t_min;
+        while let Some(rec) = self.inner.hit(r, t, t_max) {
+            if self.is_opaque(&rec) {
+                return Some(rec);
+            }
+            t = rec.t + 0.0001;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.inner.bounding_box()
+    }
+
+    // light sampling keeps picking directions over the whole shape, the ones towards cut out
+    // parts see through them like any other ray. The density of those directions is still the
+    // inner one, so is the pdf.
+    fn pdf_value(&self, o: &Vec3, v: &Vec3) -> f64 {
+        self.inner.pdf_value(o, v)
+    }
+
+    fn random(&self, o: &Vec3) -> Vec3 {
+        self.inner.random(o)
+    }
+
+    // samples on cut out parts emit nothing, which leaves the pdf over the whole shape intact
+    fn sample_surface(&self) -> Option<SurfaceSample> {
+        self.inner
+            .sample_surface()
+            .filter(|sample| self.is_sample_opaque(sample))
+    }
+
+    fn sample_uv(&self, uv: Vec2) -> Option<SurfaceSample> {
+        self.inner
+            .sample_uv(uv)
+            .filter(|sample| self.is_sample_opaque(sample))
+    }
+
+    fn area(&self) -> f64 {
+        self.inner.area()
+    }
+
+    fn power(&self) -> Vec3 {
+        self.inner.power()
+    }
+
+    fn finite_bounding_box(&self) -> Option<Aabb> {
+        self.inner.finite_bounding_box()
+    }
 }
@@ -0,0 +1,261 @@
+use std::sync::Arc;
+
+use itertools::iproduct;
+use rand::random;
+
+use crate::{
+    hit::{Aabb, HitRecord, Hitable, Material, Ray, Scatter},
+    math::{vec3, Vec2, Vec3},
+    spectrum::{luminance, upsample},
+    texture::{TexPtr, Texture},
+};
+
+use super::{intervals, Isotropic, Medium, MediumSample, PhaseFunction};
+
+pub trait DensityField: Send + Sync {
+    fn density(&self, p: &Vec3) -> f64;
+
+    // upper bound of `density` everywhere, used as the majorant for tracking
+    fn majorant(&self) -> f64;
+}
+
+// dense voxel grid stretched over `bbox`, interpolated trilinearly between voxel centres
+pub struct DensityGrid {
+    bbox: Aabb,
+    size: [usize; 3],
+    values: Vec<f64>,
+    max: f64,
+}
+
+impl DensityGrid {
+    // `values` are laid out x fastest, then y, then z
+    pub fn new(bbox: Aabb, size: [usize; 3], values: Vec<f64>) -> Self {
+        assert_eq!(
+            values.len(),
+            size[0] * size[1] * size[2],
+            "voxel count must match grid size"
+        );
+        let max = values.iter().cloned().fold(0., f64::max);
+        Self {
+            bbox,
+            size,
+            values,
+            max,
+        }
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.values[(z * self.size[1] + y) * self.size[0] + x]
+    }
+}
+
+impl DensityField for DensityGrid {
+    fn density(&self, p: &Vec3) -> f64 {
+        let local = (p - self.bbox.min()) / (self.bbox.max() - self.bbox.min());
+        if (0..3).any(|a| local[a] < 0. || local[a] > 1.) {
+            return 0.;
+        }
+
+        let mut index = [0; 3];
+        let mut frac = [0.; 3];
+        for a in 0..3 {
+            let g = (local[a] * self.size[a] as f64 - 0.5).max(0.);
+            index[a] = (g as usize).min(self.size[a] - 1);
+            frac[a] = g - index[a] as f64;
+        }
+
+        let mut density = 0.;
+        for (dx, dy, dz) in iproduct!(0..2, 0..2, 0..2) {
+            let x = (index[0] + dx).min(self.size[0] - 1);
+            let y = (index[1] + dy).min(self.size[1] - 1);
+            let z = (index[2] + dz).min(self.size[2] - 1);
+            let w = [dx, dy, dz]
+                .iter()
+                .zip(&frac)
+                .map(|(d, f)| if *d == 1 { *f } else { 1. - f })
+                .product::<f64>();
+            density += w * self.voxel(x, y, z);
+        }
+        density
+    }
+
+    fn majorant(&self) -> f64 {
+        self.max
+    }
+}
+
+// density from the luminance of a procedural texture, clamped to the given majorant
+pub struct TextureDensity {
+    texture: Arc<dyn Texture>,
+    scale: f64,
+    majorant: f64,
+}
+
+impl TextureDensity {
+    pub fn new(texture: impl TexPtr, scale: f64, majorant: f64) -> Self {
+        Self {
+            texture: texture.into(),
+            scale,
+            majorant,
+        }
+    }
+}
+
+impl DensityField for TextureDensity {
+    fn density(&self, p: &Vec3) -> f64 {
+        let value = luminance(&self.texture.value(Vec2::zero(), p));
+        (self.scale * value).max(0.).min(self.majorant)
+    }
+
+    fn majorant(&self) -> f64 {
+        self.majorant
+    }
+}
+
+// medium with a varying density inside a closed `boundary`. The boundary's surfaces mark where
+// the medium starts and ends, see `MediumStack`, and paths and shadow rays track through it
+// with delta and ratio tracking.
+pub struct HeterogeneousMedium {
+    albedo: Arc<dyn Texture>,
+    phase: Arc<dyn PhaseFunction>,
+    density: Box<dyn DensityField>,
+    boundary: Box<dyn Hitable>,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(
+        density: impl DensityField + 'static,
+        boundary: Box<dyn Hitable>,
+        texture: impl TexPtr,
+    ) -> Self {
+        Self {
+            albedo: texture.into(),
+            phase: Arc::new(Isotropic),
+            density: Box::new(density),
+            boundary,
+        }
+    }
+
+    pub fn phase(self, phase: impl PhaseFunction + 'static) -> Self {
+        Self {
+            phase: Arc::new(phase),
+            ..self
+        }
+    }
+}
+
+// delta tracking: tentative collisions at the majorant rate, accepted with density / majorant
+pub fn delta_tracking(
+    field: &(impl DensityField + ?Sized),
+    r: &Ray,
+    intervals: impl IntoIterator<Item = (f64, f64)>,
+) -> Option<f64> {
+    let majorant = field.majorant();
+    if majorant <= 0. {
+        return None;
+    }
+    let sigma = majorant * r.direction().length();
+
+    for (t0, t1) in intervals {
+        let mut t = t0;
+        loop {
+            t -= (1. - random::<f64>()).ln() / sigma;
+            if t >= t1 {
+                break;
+            }
+            if random::<f64>() * majorant < field.density(&r.at(t)) {
+                return Some(t);
+            }
+        }
+    }
+    None
+}
+
+impl Medium for HeterogeneousMedium {
+    fn sample(&self, r: &Ray, t_max: f64) -> MediumSample {
+        // clipped to the boundary, should the path stray out of it at a grazing crossing
+        let intervals = intervals(self.boundary.as_ref(), r, 0., t_max);
+        match delta_tracking(self.density.as_ref(), r, intervals) {
+            Some(t) => {
+                let albedo = self.albedo.value(Vec2::zero(), &r.at(t));
+                MediumSample {
+                    t: Some(t),
+                    weight: match r.wavelengths() {
+                        Some(lambdas) => upsample(&albedo, &lambdas),
+                        None => albedo,
+                    },
+                }
+            }
+            None => MediumSample {
+                t: None,
+                weight: vec3(1, 1, 1),
+            },
+        }
+    }
+
+    // ratio tracking
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec3 {
+        let majorant = self.density.majorant();
+        if majorant <= 0. {
+            return vec3(1, 1, 1);
+        }
+        let sigma = majorant * r.direction().length();
+
+        let mut transmittance = 1.;
+        for (t0, t1) in intervals(self.boundary.as_ref(), r, t_min, t_max) {
+            let mut t = t0;
+            loop {
+                t -= (1. - random::<f64>()).ln() / sigma;
+                if t >= t1 {
+                    break;
+                }
+                transmittance *= 1. - self.density.density(&r.at(t)) / majorant;
+            }
+        }
+        Vec3::new1(transmittance)
+    }
+
+    fn phase_function(&self) -> &Arc<dyn PhaseFunction> {
+        &self.phase
+    }
+}
+
+// the boundary's surfaces only let paths into and out of the medium
+impl Material for HeterogeneousMedium {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
+        Some(Scatter::new_specular(
+            Ray::new(rec.p, *r_in.direction()).with_differentials(r_in.differentials()),
+            vec3(1, 1, 1),
+        ))
+    }
+
+    fn interior(&self) -> Option<&dyn Medium> {
+        Some(self)
+    }
+
+    fn is_interface(&self) -> bool {
+        true
+    }
+}
+
+impl Hitable for HeterogeneousMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let rec = self.boundary.hit(r, t_min, t_max)?;
+        Some(HitRecord {
+            material: self,
+            ..rec
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn pdf_value(&self, _o: &Vec3, _v: &Vec3) -> f64 {
+        0.0
+    }
+
+    fn random(&self, _o: &Vec3) -> Vec3 {
+        vec3(1, 0, 0)
+    }
+}
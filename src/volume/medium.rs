@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use rand::random;
+
+use crate::{
+    hit::{HitRecord, Material, Ray, Scatter},
+    math::{vec3, Vec3},
+    spectrum::upsample,
+};
+
+use super::{Isotropic, PhaseFunction};
+
+// outcome of flying through a medium: either a scattering event at `t`, or the ray reached
+// `t_max`. `weight` is the throughput of the flight divided by its sampling probability.
+pub struct MediumSample {
+    pub t: Option<f64>,
+    pub weight: Vec3,
+}
+
+// participating media filling the inside of a surface, see `Material::interior`
+pub trait Medium: Send + Sync {
+    fn sample(&self, r: &Ray, t_max: f64) -> MediumSample;
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec3;
+
+    fn phase_function(&self) -> &Arc<dyn PhaseFunction>;
+}
+
+// constant rgb absorption and scattering coefficients, per unit of distance
+pub struct HomogeneousMedium {
+    sigma_a: Vec3,
+    sigma_s: Vec3,
+    phase: Arc<dyn PhaseFunction>,
+}
+
+impl HomogeneousMedium {
+    pub fn new(sigma_a: Vec3, sigma_s: Vec3) -> Self {
+        Self {
+            sigma_a,
+            sigma_s,
+            phase: Arc::new(Isotropic),
+        }
+    }
+
+    // purely absorbing medium that tints light to `color` after travelling `distance`
+    pub fn absorbing(color: Vec3, distance: f64) -> Self {
+        let sigma_a = color.map(|c| -c.max(1e-6).ln() / distance);
+        Self::new(sigma_a, Vec3::zero())
+    }
+
+    pub fn phase(self, phase: impl PhaseFunction + 'static) -> Self {
+        Self {
+            phase: Arc::new(phase),
+            ..self
+        }
+    }
+
+    // absorption and scattering at the wavelengths of spectral paths
+    fn coefficients(&self, r: &Ray) -> (Vec3, Vec3) {
+        match r.wavelengths() {
+            Some(lambdas) => (
+                upsample(&self.sigma_a, &lambdas),
+                upsample(&self.sigma_s, &lambdas),
+            ),
+            None => (self.sigma_a, self.sigma_s),
+        }
+    }
+}
+
+impl Medium for HomogeneousMedium {
+    // free flights follow sigma_s of a randomly picked channel, absorption only enters the
+    // weight, so clear or purely absorbing media never waste samples on collisions
+    fn sample(&self, r: &Ray, t_max: f64) -> MediumSample {
+        let (sigma_a, sigma_s) = self.coefficients(r);
+        let speed = r.direction().length();
+        let channel = ((random::<f64>() * 3.) as usize).min(2);
+        let sigma = sigma_s[channel];
+
+        let distance = if sigma > 0. {
+            -(1. - random::<f64>()).ln() / sigma
+        } else {
+            f64::INFINITY
+        };
+        let scattered = distance < t_max * speed;
+        let distance = distance.min(t_max * speed);
+
+        let transmittance = (-(sigma_a + sigma_s) * distance).map(f64::exp);
+        let scatter_free = (-sigma_s * distance).map(f64::exp);
+        if scattered {
+            let pdf = (sigma_s * scatter_free).mean();
+            MediumSample {
+                t: Some(distance / speed),
+                weight: sigma_s * transmittance / pdf,
+            }
+        } else {
+            let pdf = scatter_free.mean();
+            MediumSample {
+                t: None,
+                weight: if pdf > 0. {
+                    transmittance / pdf
+                } else {
+                    Vec3::zero()
+                },
+            }
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> Vec3 {
+        let (sigma_a, sigma_s) = self.coefficients(r);
+        let distance = (t_max - t_min).max(0.) * r.direction().length();
+        (-(sigma_a + sigma_s) * distance).map(f64::exp)
+    }
+
+    fn phase_function(&self) -> &Arc<dyn PhaseFunction> {
+        &self.phase
+    }
+}
+
+// invisible surface that only marks where a medium begins and ends
+pub struct MediumBoundary {
+    interior: Arc<dyn Medium>,
+    priority: u32,
+}
+
+impl MediumBoundary {
+    pub fn new(interior: impl Medium + 'static) -> Self {
+        Self {
+            interior: Arc::new(interior),
+            priority: 0,
+        }
+    }
+
+    pub fn priority(self, priority: u32) -> Self {
+        Self { priority, ..self }
+    }
+}
+
+impl Material for MediumBoundary {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
+        Some(Scatter::new_specular(
+            Ray::new(rec.p, *r_in.direction()).with_differentials(r_in.differentials()),
+            vec3(1, 1, 1),
+        ))
+    }
+
+    fn interior(&self) -> Option<&dyn Medium> {
+        Some(self.interior.as_ref())
+    }
+
+    fn is_interface(&self) -> bool {
+        true
+    }
+
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+}
@@ -0,0 +1,101 @@
+use rand::random;
+
+use crate::{
+    hit::{HitRecord, Hitable, Ray},
+    math::{vec3, Vec2, Vec3},
+    texture::TexPtr,
+};
+
+mod phase;
+pub use phase::*;
+
+mod medium;
+pub use medium::*;
+
+mod stack;
+pub use stack::*;
+
+mod heterogeneous;
+pub use heterogeneous::*;
+
+mod sparse;
+pub use sparse::*;
+
+// entry and exit distances of every stretch of `r` that lies inside the closed `boundary`
+pub fn intervals(boundary: &dyn Hitable, r: &Ray, t_min: f64, t_max: f64) -> Vec<(f64, f64)> {
+    let mut intervals = Vec::new();
+    let mut entry = None;
+    let mut t = t_min;
+
+    while let Some(rec) = boundary.hit(r, t, t_max) {
+        if rec.front_face {
+            entry = Some(rec.t);
+        } else {
+            intervals.push((entry.unwrap_or(t_min), rec.t));
+            entry = None;
+        }
+        t = rec.t + 0.0001;
+    }
+    if let Some(entry) = entry {
+        intervals.push((entry, t_max));
+    }
+
+    intervals
+}
+
+pub struct ConstantMedium {
+    phase_function: PhaseMaterial,
+    density: f64,
+    boundary: Box<dyn Hitable>,
+}
+
+impl ConstantMedium {
+    pub fn new(density: f64, boundary: Box<dyn Hitable>, texture: impl TexPtr) -> Self {
+        Self {
+            phase_function: PhaseMaterial::new(texture, Isotropic),
+            density,
+            boundary,
+        }
+    }
+
+    pub fn phase(self, phase: impl PhaseFunction + 'static) -> Self {
+        Self {
+            phase_function: self.phase_function.phase(phase),
+            ..self
+        }
+    }
+}
+
+impl Hitable for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        for (t0, t1) in intervals(self.boundary.as_ref(), r, t_min, t_max) {
+            let distance_inside_boundary = (t1 - t0) * r.direction().length();
+            let hit_distance = -(1. / self.density) * random::<f64>().ln();
+            if hit_distance < distance_inside_boundary {
+                let t = t0 + hit_distance / r.direction().length();
+                let p = r.at(t);
+                return Some(HitRecord::new(
+                    r,
+                    t,
+                    p,
+                    vec3(1., 0., 0.),
+                    Vec2::zero(),
+                    &self.phase_function,
+                ));
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> crate::hit::Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn pdf_value(&self, _o: &Vec3, _v: &Vec3) -> f64 {
+        0.0
+    }
+
+    fn random(&self, _o: &Vec3) -> Vec3 {
+        vec3(1, 0, 0)
+    }
+}
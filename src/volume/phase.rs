@@ -0,0 +1,196 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use rand::random;
+
+use crate::{
+    hit::{HitRecord, Material, Pdf, Ray, Scatter},
+    math::{dot, vec3, Onb, Vec3},
+    texture::{TexPtr, Texture},
+};
+
+// angular distribution of light scattered inside a medium. `cos_theta` is measured between
+// the direction the light travelled in and the direction it leaves in, so g > 0 is forward.
+pub trait PhaseFunction: Send + Sync {
+    fn value(&self, cos_theta: f64) -> f64;
+
+    fn sample_cos_theta(&self) -> f64;
+
+    // new direction of travel for light arriving along `direction`
+    fn sample(&self, direction: &Vec3) -> Vec3 {
+        let cos_theta = self.sample_cos_theta().clamp(-1., 1.);
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * random::<f64>();
+        Onb::build_from(direction).local(&vec3(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+}
+
+pub struct Isotropic;
+
+impl PhaseFunction for Isotropic {
+    fn value(&self, _cos_theta: f64) -> f64 {
+        1. / (4. * PI)
+    }
+
+    fn sample_cos_theta(&self) -> f64 {
+        1. - 2. * random::<f64>()
+    }
+}
+
+pub struct HenyeyGreenstein {
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    // asymmetry in (-1, 1), the mean cosine of the scattering angle
+    pub fn new(g: f64) -> Self {
+        Self {
+            g: g.clamp(-0.999, 0.999),
+        }
+    }
+}
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn value(&self, cos_theta: f64) -> f64 {
+        let g = self.g;
+        let denom = 1. + g * g - 2. * g * cos_theta;
+        (1. - g * g) / (4. * PI * denom * denom.sqrt())
+    }
+
+    fn sample_cos_theta(&self) -> f64 {
+        let (g, u) = (self.g, random::<f64>());
+        if g.abs() < 1e-3 {
+            return 1. - 2. * u;
+        }
+        let s = (1. - g * g) / (1. - g + 2. * g * u);
+        (1. + g * g - s * s) / (2. * g)
+    }
+}
+
+// blend of a forward and a backward lobe, a cheap fit of the Mie phase function of haze,
+// clouds and other media with particles around the size of the wavelength
+pub struct DoubleHenyeyGreenstein {
+    forward: HenyeyGreenstein,
+    backward: HenyeyGreenstein,
+    weight: f64,
+}
+
+impl DoubleHenyeyGreenstein {
+    // `weight` is the share of the forward lobe
+    pub fn new(g_forward: f64, g_backward: f64, weight: f64) -> Self {
+        Self {
+            forward: HenyeyGreenstein::new(g_forward),
+            backward: HenyeyGreenstein::new(g_backward),
+            weight: weight.clamp(0., 1.),
+        }
+    }
+
+    pub fn cloud() -> Self {
+        Self::new(0.8, -0.3, 0.9)
+    }
+}
+
+impl PhaseFunction for DoubleHenyeyGreenstein {
+    fn value(&self, cos_theta: f64) -> f64 {
+        self.weight * self.forward.value(cos_theta)
+            + (1. - self.weight) * self.backward.value(cos_theta)
+    }
+
+    fn sample_cos_theta(&self) -> f64 {
+        if random::<f64>() < self.weight {
+            self.forward.sample_cos_theta()
+        } else {
+            self.backward.sample_cos_theta()
+        }
+    }
+}
+
+// scattering by particles much smaller than the wavelength, e.g. air molecules
+pub struct Rayleigh;
+
+impl PhaseFunction for Rayleigh {
+    fn value(&self, cos_theta: f64) -> f64 {
+        3. / (16. * PI) * (1. + cos_theta * cos_theta)
+    }
+
+    // inverts the cdf (x^3 + 3x + 4) / 8 with Cardano's formula
+    fn sample_cos_theta(&self) -> f64 {
+        let q = 4. - 8. * random::<f64>();
+        let d = (0.25 * q * q + 1.).sqrt();
+        (-0.5 * q + d).cbrt() + (-0.5 * q - d).cbrt()
+    }
+}
+
+pub struct PhasePdf {
+    phase: Arc<dyn PhaseFunction>,
+    direction: Vec3,
+}
+
+impl PhasePdf {
+    pub fn new(phase: Arc<dyn PhaseFunction>, direction: &Vec3) -> Self {
+        Self {
+            phase,
+            direction: direction.normalize(),
+        }
+    }
+}
+
+impl Pdf for PhasePdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.phase
+            .value(dot(&self.direction, &direction.normalize()))
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.phase.sample(&self.direction)
+    }
+}
+
+// material of the scattering events inside a medium
+pub struct PhaseMaterial {
+    albedo: Arc<dyn Texture>,
+    phase: Arc<dyn PhaseFunction>,
+}
+
+impl PhaseMaterial {
+    pub fn new(albedo: impl TexPtr, phase: impl PhaseFunction + 'static) -> Self {
+        Self {
+            albedo: albedo.into(),
+            phase: Arc::new(phase),
+        }
+    }
+
+    pub fn phase(self, phase: impl PhaseFunction + 'static) -> Self {
+        Self {
+            phase: Arc::new(phase),
+            ..self
+        }
+    }
+}
+
+impl Material for PhaseMaterial {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
+        Some(Scatter::new_diffuse(
+            self.albedo.value(rec.uv, &rec.p),
+            Box::new(PhasePdf::new(self.phase.clone(), r_in.direction())),
+        ))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, _rec: &HitRecord, scattered: &Ray) -> f64 {
+        phase_value(self.phase.as_ref(), r_in, scattered)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
+
+pub fn phase_value(phase: &dyn PhaseFunction, r_in: &Ray, scattered: &Ray) -> f64 {
+    phase.value(dot(
+        &r_in.direction().normalize(),
+        &scattered.direction().normalize(),
+    ))
+}
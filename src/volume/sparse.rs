@@ -0,0 +1,395 @@
+// Sparse brick grids, as written by simulation cache exporters.
+//
+// The file is little endian:
+//
+// ```text
+// magic        [u8; 4]   "RTVB"
+// version      u32       1
+// voxel size   f32       edge length of a voxel in world units
+// origin       [f32; 3]  world position of the lower corner of voxel (0, 0, 0)
+// brick count  u32
+// per brick:
+//   coord        [i32; 3]    brick index, voxel = 8 * coord + local
+//   density      [f32; 512]  x fastest, then y, then z
+//   temperature  [f32; 512]  kelvin, same layout
+// ```
+//
+// Voxels outside every brick have zero density and zero temperature.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+    sync::Arc,
+};
+
+use itertools::iproduct;
+
+use crate::{
+    hit::{Aabb, HitRecord, Hitable, Material, Ray, Scatter},
+    math::{vec3, Vec2, Vec3},
+    spectrum::{blackbody, spectrum_to_xyz, xyz_to_rgb},
+};
+
+use super::{delta_tracking, phase_value, DensityField, Isotropic, PhaseFunction, PhasePdf};
+
+const MAGIC: &[u8; 4] = b"RTVB";
+const VERSION: u32 = 1;
+const BRICK: i32 = 8;
+const BRICK_VOXELS: usize = (BRICK * BRICK * BRICK) as usize;
+
+struct Brick {
+    density: Vec<f32>,
+    temperature: Vec<f32>,
+}
+
+pub struct SparseGrid {
+    voxel_size: f64,
+    origin: Vec3,
+    bricks: HashMap<[i32; 3], Brick>,
+    bbox: Aabb,
+    max_density: f64,
+    max_temperature: f64,
+}
+
+impl SparseGrid {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read(mut reader: impl Read) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a sparse volume file"));
+        }
+        if read_u32(&mut reader)? != VERSION {
+            return Err(invalid_data("unsupported sparse volume version"));
+        }
+
+        let voxel_size = read_f32(&mut reader)? as f64;
+        if voxel_size.is_nan() || voxel_size <= 0. {
+            return Err(invalid_data("invalid voxel size"));
+        }
+        let origin = vec3(
+            read_f32(&mut reader)?,
+            read_f32(&mut reader)?,
+            read_f32(&mut reader)?,
+        );
+
+        // the count comes from the file, the map only grows as bricks are actually read
+        let count = read_u32(&mut reader)? as usize;
+        let mut bricks = HashMap::new();
+        let (mut lo, mut hi) = ([i32::MAX; 3], [i32::MIN; 3]);
+        let (mut max_density, mut max_temperature) = (0f64, 0f64);
+        let truncated = |e: io::Error| match e.kind() {
+            io::ErrorKind::UnexpectedEof => invalid_data("fewer bricks than the header declares"),
+            _ => e,
+        };
+        for _ in 0..count {
+            let coord = [
+                read_i32(&mut reader).map_err(truncated)?,
+                read_i32(&mut reader).map_err(truncated)?,
+                read_i32(&mut reader).map_err(truncated)?,
+            ];
+            let density = read_f32s(&mut reader, BRICK_VOXELS).map_err(truncated)?;
+            let temperature = read_f32s(&mut reader, BRICK_VOXELS).map_err(truncated)?;
+
+            max_density = density.iter().fold(max_density, |m, &d| m.max(d as f64));
+            max_temperature = temperature
+                .iter()
+                .fold(max_temperature, |m, &t| m.max(t as f64));
+            for (a, c) in coord.iter().enumerate() {
+                let end = c
+                    .checked_add(1)
+                    .ok_or_else(|| invalid_data("brick coordinate out of range"))?;
+                lo[a] = lo[a].min(*c);
+                hi[a] = hi[a].max(end);
+            }
+            let brick = Brick {
+                density,
+                temperature,
+            };
+            if bricks.insert(coord, brick).is_some() {
+                return Err(invalid_data("duplicate brick"));
+            }
+        }
+        if reader.read(&mut [0u8])? != 0 {
+            return Err(invalid_data("more bricks than the header declares"));
+        }
+
+        let bbox = if bricks.is_empty() {
+            Aabb::new(origin, origin)
+        } else {
+            let extent = BRICK as f64 * voxel_size;
+            let corner = |c: [i32; 3]| vec3(c[0], c[1], c[2]) * extent + origin;
+            Aabb::new(corner(lo), corner(hi))
+        };
+
+        Ok(Self {
+            voxel_size,
+            origin,
+            bricks,
+            bbox,
+            max_density,
+            max_temperature,
+        })
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    pub fn max_density(&self) -> f64 {
+        self.max_density
+    }
+
+    pub fn max_temperature(&self) -> f64 {
+        self.max_temperature
+    }
+
+    pub fn temperature(&self, p: &Vec3) -> f64 {
+        self.interpolate(p, |brick| &brick.temperature)
+    }
+
+    fn voxel(&self, v: [i32; 3], channel: impl Fn(&Brick) -> &[f32]) -> f64 {
+        let coord = [
+            v[0].div_euclid(BRICK),
+            v[1].div_euclid(BRICK),
+            v[2].div_euclid(BRICK),
+        ];
+        self.bricks.get(&coord).map_or(0., |brick| {
+            let [x, y, z] = [
+                v[0].rem_euclid(BRICK),
+                v[1].rem_euclid(BRICK),
+                v[2].rem_euclid(BRICK),
+            ];
+            channel(brick)[((z * BRICK + y) * BRICK + x) as usize] as f64
+        })
+    }
+
+    // trilinear between voxel centres
+    fn interpolate(&self, p: &Vec3, channel: impl Fn(&Brick) -> &[f32] + Copy) -> f64 {
+        let g = (p - self.origin) / self.voxel_size - vec3(0.5, 0.5, 0.5);
+        let base = [g.x().floor(), g.y().floor(), g.z().floor()];
+        let frac = [g.x() - base[0], g.y() - base[1], g.z() - base[2]];
+
+        iproduct!(0..2, 0..2, 0..2)
+            .map(|(dx, dy, dz)| {
+                let d = [dx, dy, dz];
+                let w: f64 = (0..3)
+                    .map(|a| if d[a] == 1 { frac[a] } else { 1. - frac[a] })
+                    .product();
+                if w == 0. {
+                    return 0.;
+                }
+                let v = [
+                    base[0] as i32 + dx,
+                    base[1] as i32 + dy,
+                    base[2] as i32 + dz,
+                ];
+                w * self.voxel(v, channel)
+            })
+            .sum()
+    }
+}
+
+impl DensityField for SparseGrid {
+    fn density(&self, p: &Vec3) -> f64 {
+        self.interpolate(p, |brick| &brick.density)
+    }
+
+    fn majorant(&self) -> f64 {
+        self.max_density
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_i32(reader: &mut impl Read) -> io::Result<i32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(f32::from_le_bytes(buf))
+}
+
+fn read_f32s(reader: &mut impl Read, n: usize) -> io::Result<Vec<f32>> {
+    let mut buf = vec![0u8; 4 * n];
+    reader.read_exact(&mut buf)?;
+    Ok(buf
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+// rgb radiance of a blackbody, tabulated up to the hottest voxel of the grid
+struct BlackbodyTable {
+    step: f64,
+    rgb: Vec<Vec3>,
+}
+
+impl BlackbodyTable {
+    const ENTRIES: usize = 256;
+
+    fn new(max_temperature: f64) -> Self {
+        let step = max_temperature.max(1.) / (Self::ENTRIES - 1) as f64;
+        let rgb = (0..Self::ENTRIES)
+            .map(|i| {
+                let kelvin = i as f64 * step;
+                if kelvin <= 0. {
+                    return Vec3::zero();
+                }
+                // Planck's law per nm rather than per m
+                let xyz = spectrum_to_xyz(|lambda| 1e-9 * blackbody(lambda, kelvin));
+                xyz_to_rgb(&xyz).map(|c| c.max(0.))
+            })
+            .collect();
+        Self { step, rgb }
+    }
+
+    fn value(&self, kelvin: f64) -> Vec3 {
+        let x = (kelvin / self.step).max(0.);
+        let i = (x as usize).min(self.rgb.len() - 2);
+        let f = (x - i as f64).min(1.);
+        (1. - f) * self.rgb[i] + f * self.rgb[i + 1]
+    }
+}
+
+struct GridPhase {
+    grid: Arc<SparseGrid>,
+    albedo: Vec3,
+    phase: Arc<dyn PhaseFunction>,
+    emission: f64,
+    blackbody: BlackbodyTable,
+}
+
+impl Material for GridPhase {
+    fn scatter(&self, r_in: &Ray, _rec: &HitRecord) -> Option<Scatter> {
+        Some(Scatter::new_diffuse(
+            self.albedo,
+            Box::new(PhasePdf::new(self.phase.clone(), r_in.direction())),
+        ))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, _rec: &HitRecord, scattered: &Ray) -> f64 {
+        phase_value(self.phase.as_ref(), r_in, scattered)
+    }
+
+    // at a collision the absorbed fraction sigma_a / sigma_t is re-emitted as blackbody radiance
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord, _uv: Vec2, p: &Vec3) -> Vec3 {
+        if self.emission <= 0. {
+            return Vec3::zero();
+        }
+        let absorbed = Vec3::new(1., 1., 1.) - self.albedo;
+        self.emission * absorbed * self.blackbody.value(self.grid.temperature(p))
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+}
+
+// medium filling the bricks of a sparse grid, sigma_t = density_scale * density
+pub struct GridMedium {
+    grid: Arc<SparseGrid>,
+    density_scale: f64,
+    phase_function: GridPhase,
+}
+
+impl GridMedium {
+    pub fn new(grid: SparseGrid, density_scale: f64, albedo: Vec3) -> Self {
+        let grid = Arc::new(grid);
+        Self {
+            density_scale,
+            phase_function: GridPhase {
+                blackbody: BlackbodyTable::new(grid.max_temperature()),
+                grid: grid.clone(),
+                albedo,
+                phase: Arc::new(Isotropic),
+                emission: 0.,
+            },
+            grid,
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>, density_scale: f64, albedo: Vec3) -> io::Result<Self> {
+        Ok(Self::new(SparseGrid::open(path)?, density_scale, albedo))
+    }
+
+    // scale applied to the blackbody radiance of the temperature channel, 0 disables emission
+    pub fn emission(self, emission: f64) -> Self {
+        Self {
+            phase_function: GridPhase {
+                emission,
+                ..self.phase_function
+            },
+            ..self
+        }
+    }
+
+    pub fn phase(self, phase: impl PhaseFunction + 'static) -> Self {
+        Self {
+            phase_function: GridPhase {
+                phase: Arc::new(phase),
+                ..self.phase_function
+            },
+            ..self
+        }
+    }
+
+    pub fn grid(&self) -> &SparseGrid {
+        &self.grid
+    }
+}
+
+impl DensityField for GridMedium {
+    fn density(&self, p: &Vec3) -> f64 {
+        self.density_scale * self.grid.density(p)
+    }
+
+    fn majorant(&self) -> f64 {
+        self.density_scale * self.grid.majorant()
+    }
+}
+
+impl Hitable for GridMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let interval = self.grid.bounding_box().interval(r, t_min, t_max)?;
+        let t = delta_tracking(self, r, Some(interval))?;
+
+        Some(HitRecord::new(
+            r,
+            t,
+            r.at(t),
+            vec3(1., 0., 0.),
+            Vec2::zero(),
+            &self.phase_function,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.grid.bounding_box()
+    }
+
+    fn pdf_value(&self, _o: &Vec3, _v: &Vec3) -> f64 {
+        0.0
+    }
+
+    fn random(&self, _o: &Vec3) -> Vec3 {
+        vec3(1, 0, 0)
+    }
+}
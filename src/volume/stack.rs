@@ -0,0 +1,103 @@
+use crate::{
+    hit::{HitRecord, Material},
+    math::{dot, Vec3},
+};
+
+use super::Medium;
+
+// Surfaces a path is currently inside of, for nested dielectrics and media. Where volumes
+// overlap, the one with the highest `Material::priority` wins and hits on the others are
+// false intersections that the path passes straight through. Objects sharing a material are
+// entered once each, and where they overlap they act as one volume.
+//
+// Which side of a surface is inside comes from its hit record's `front_face`, so volumes have to
+// be bounded by outward facing shapes: a sphere with a negative radius is inside out.
+#[derive(Clone, Default)]
+pub struct MediumStack<'m> {
+    entries: Vec<&'m dyn Material>,
+}
+
+impl<'m> MediumStack<'m> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn medium(&self) -> Option<&'m dyn Medium> {
+        self.top(None).and_then(|material| material.interior())
+    }
+
+    // index of refraction surrounding `material`, 1 for vacuum
+    pub fn outer_ior(&self, material: &dyn Material) -> f64 {
+        self.entries
+            .iter()
+            .filter(|entry| !same(**entry, material) && entry.ior().is_some())
+            .max_by_key(|entry| entry.priority())
+            .and_then(|entry| entry.ior())
+            .unwrap_or(1.)
+    }
+
+    pub fn is_false_intersection(&self, rec: &HitRecord) -> bool {
+        if !participates(rec.material) {
+            return false;
+        }
+        // entering a volume the path is already in, or leaving one it is still in through another
+        // object of the same material
+        let depth = self.depth(rec.material);
+        if (rec.front_face && depth > 0) || depth > 1 {
+            return true;
+        }
+        match self.top(Some(rec.material)) {
+            Some(top) => top.priority() > rec.material.priority(),
+            None => false,
+        }
+    }
+
+    // the stack of a path that arrived at `rec` along `incoming` and leaves along `outgoing`
+    pub fn cross(&self, rec: &HitRecord<'m>, incoming: &Vec3, outgoing: &Vec3) -> Self {
+        let material = rec.material;
+        // a reflected path stays on the side it came from, whichever way the normal points
+        let crossed = dot(incoming, &rec.normal) * dot(outgoing, &rec.normal) > 0.;
+        if !participates(material) || !crossed {
+            return self.clone();
+        }
+
+        let mut entries = self.entries.clone();
+        if rec.front_face {
+            entries.push(material);
+        } else if let Some(position) = entries.iter().rposition(|entry| same(*entry, material)) {
+            entries.remove(position);
+        }
+        Self { entries }
+    }
+
+    // how many objects of `material` the path is inside of
+    fn depth(&self, material: &dyn Material) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| same(**entry, material))
+            .count()
+    }
+
+    // highest priority entry, later entries win ties
+    fn top(&self, skip: Option<&dyn Material>) -> Option<&'m dyn Material> {
+        self.entries
+            .iter()
+            .filter(|entry| match skip {
+                Some(skip) => !same(**entry, skip),
+                None => true,
+            })
+            .max_by_key(|entry| entry.priority())
+            .copied()
+    }
+}
+
+fn participates(material: &dyn Material) -> bool {
+    material.ior().is_some() || material.interior().is_some()
+}
+
+fn same(a: &dyn Material, b: &dyn Material) -> bool {
+    std::ptr::eq(
+        a as *const dyn Material as *const u8,
+        b as *const dyn Material as *const u8,
+    )
+}